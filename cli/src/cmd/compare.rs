@@ -0,0 +1,31 @@
+use clap::Args;
+use core::universe::types::Universe;
+use std::process;
+
+#[derive(Args, Debug)]
+pub struct CompareCmd {
+    /// the first state file, or a snapshot file saved with run --save
+    #[clap(value_name = "A", index = 1)]
+    a: String,
+
+    /// the second state file, or a snapshot file saved with run --save
+    #[clap(value_name = "B", index = 2)]
+    b: String,
+}
+
+// compare prints as Json the inner product ⟨A|B⟩, the fidelity and the trace distance
+// between the combined states of the universes, and the number and total probability
+// of the configurations of each universe that are not in the other one
+pub fn compare(args: &CompareCmd) {
+    let load = |file: &str| match Universe::load(file) {
+        Ok(universe) => universe,
+        Err(err) => {
+            eprintln!("error: can't load universe from {file}: {err}");
+            process::exit(1);
+        }
+    };
+    let a = load(&args.a);
+    let b = load(&args.b);
+
+    println!("{}", serde_json::to_string(&a.compare(&b)).unwrap());
+}
@@ -0,0 +1,98 @@
+use clap::{Args, ValueEnum};
+use core::universe::types::{Coordinates, Universe};
+use std::fs;
+use std::process;
+
+#[derive(Args, Debug)]
+pub struct DensityMatrixCmd {
+    /// the state file, or a snapshot file saved with run --save
+    #[clap(value_name = "STATE_FILE", index = 1)]
+    state_file: String,
+
+    /// a cell of the window, as x,y (at most 10 cells), the bit k of the indexes
+    /// of the matrix is 1 if the k-th cell is alive
+    #[clap(short, long = "cell", value_name = "X,Y", value_parser = parse_cell, allow_hyphen_values = true, required = true)]
+    cells: Vec<Coordinates>,
+
+    /// the rules file (JSON or TOML), the default rules (or the rules of the snapshot)
+    /// are used if not provided
+    #[clap(short, long, value_parser)]
+    rules: Option<String>,
+
+    /// number of steps to compute before computing the matrix
+    #[clap(short, long, value_parser, default_value_t = 0)]
+    steps: usize,
+
+    /// format of the matrix: Json, or CSV with one row,column,re,im line per entry
+    #[clap(short, long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// write the matrix in a file instead of printing it
+    #[clap(short, long, value_parser)]
+    out: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+fn parse_cell(value: &str) -> Result<Coordinates, String> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or_else(|| format!("{value} is not a x,y cell"))?;
+    let parse = |coordinate: &str| {
+        coordinate
+            .trim()
+            .parse::<i32>()
+            .map_err(|err| format!("invalid coordinate {coordinate}: {err}"))
+    };
+    Ok(Coordinates {
+        x: parse(x)?,
+        y: parse(y)?,
+    })
+}
+
+pub fn density_matrix(args: &DensityMatrixCmd) {
+    let universe = match &args.rules {
+        Some(_) => Universe::new_from_files(&args.state_file, args.rules.as_deref()),
+        None => Universe::load(&args.state_file),
+    };
+    let mut universe = match universe {
+        Ok(universe) => universe,
+        Err(err) => {
+            eprintln!("error: can't load universe from {}: {err}", args.state_file);
+            process::exit(1);
+        }
+    };
+
+    for _ in 0..args.steps {
+        if let Err(err) = universe.step() {
+            eprintln!("error: can't compute step {}: {err}", universe.step_count);
+            process::exit(1);
+        }
+    }
+
+    let matrix = match universe.reduced_density_matrix(&args.cells) {
+        Ok(matrix) => matrix,
+        Err(err) => {
+            eprintln!("error: can't compute the density matrix: {err}");
+            process::exit(1);
+        }
+    };
+    let serialized = match args.format {
+        Format::Json => serde_json::to_string(&matrix).unwrap(),
+        Format::Csv => matrix.to_csv(),
+    };
+
+    match &args.out {
+        Some(out) => {
+            if let Err(err) = fs::write(out, serialized) {
+                eprintln!("error: can't write {out}: {err}");
+                process::exit(1);
+            }
+        }
+        None => println!("{}", serialized.trim_end()),
+    }
+}
@@ -1,48 +1,197 @@
-use clap::Args;
-use core::universe::types;
+use clap::{Args, ValueEnum};
+use core::universe::cell_set::CellSet;
+use core::universe::{rules, types};
 use num::complex::Complex;
-use rand::Rng;
-use std::collections::HashMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashSet;
+use std::fs;
+use std::process;
 
 #[derive(Args, Debug)]
 pub struct GenCmd {
-    /// generate a state, which consist of a list of configurations
-    #[clap(short, long, value_parser)]
+    /// generate a state, which consist of a list of configurations (default)
+    #[clap(short, long, value_parser, conflicts_with = "rules")]
     state: bool,
 
+    /// generate a rules file containing the default rules
+    #[clap(short, long, value_parser)]
+    rules: bool,
+
     /// store generated data in a Json file
     #[clap(short, long, value_parser)]
     out: Option<String>,
+
+    /// pattern of the living cells
+    #[clap(short, long, value_enum, default_value_t = Pattern::Random)]
+    pattern: Pattern,
+
+    /// x coordinate of the top left corner of the grid containing the pattern
+    #[clap(short, value_parser, default_value_t = 0, allow_hyphen_values = true)]
+    x: i32,
+
+    /// y coordinate of the top left corner of the grid containing the pattern
+    #[clap(short, value_parser, default_value_t = 0, allow_hyphen_values = true)]
+    y: i32,
+
+    /// width of the grid containing the pattern
+    #[clap(short, long, value_parser, default_value_t = 100)]
+    width: i32,
+
+    /// height of the grid containing the pattern
+    #[clap(long, value_parser, default_value_t = 100)]
+    height: i32,
+
+    /// number of living cells of the random patterns
+    /// (a random number lower than 100 if neither cells nor density is provided)
+    #[clap(short, long, value_parser, conflicts_with = "density")]
+    cells: Option<usize>,
+
+    /// probability of each cell of the grid to be alive in the random patterns,
+    /// between 0 and 1
+    #[clap(short, long, value_parser = parse_density)]
+    density: Option<f64>,
+
+    /// number of superposed configurations of the superposition pattern,
+    /// identical random configurations are only kept once
+    #[clap(long, value_parser = parse_configurations, default_value_t = 2)]
+    configurations: usize,
+
+    /// seed of the random number generator, for reproducible generation
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
 }
 
-pub fn generate(_cmd: &GenCmd) {
-    let universe = types::Universe::new();
-    let mut state = universe.state;
+#[derive(ValueEnum, Clone, Debug)]
+pub enum Pattern {
+    /// living cells chosen randomly in the grid
+    Random,
+    /// every cell of the grid is alive
+    Block,
+    /// horizontal line on the first row of the grid
+    Line,
+    /// two diagonal cells at the top left corner of the grid
+    Diagonal,
+    /// uniform superposition of random configurations
+    Superposition,
+}
 
-    let mut configuration = types::Configuration {
-        //The module of the amplitude should be equal to 1
-        amplitude: Complex::new(1., 0.),
-        living_cells: HashMap::new(),
+fn parse_density(value: &str) -> Result<f64, String> {
+    let density: f64 = value.parse().map_err(|err| format!("{err}"))?;
+    if (0. ..=1.).contains(&density) {
+        Ok(density)
+    } else {
+        Err(format!("{value} is not between 0 and 1"))
+    }
+}
+
+fn parse_configurations(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("at least one configuration is needed".to_string()),
+        Ok(configurations) => Ok(configurations),
+        Err(err) => Err(format!("{err}")),
+    }
+}
+
+pub fn generate(cmd: &GenCmd) {
+    let serialized = if cmd.rules {
+        let rules_file = rules::RulesFile::from(&[rules::get_default_rules()][..]);
+        serde_json::to_string(&rules_file).unwrap()
+    } else {
+        serde_json::to_string(&generate_state(cmd)).unwrap()
     };
 
-    //Generate a set of living cells
-    let max_x = 100;
-    let max_y = 100;
-    let max_number_of_living_cells = 100;
-    let mut rng = rand::thread_rng();
-    //Get the number of cells we would like to have on the map
-    let number_of_cells: i32 = rng.gen_range(0..max_number_of_living_cells);
-    for _i in 0..number_of_cells {
-        configuration.living_cells.insert(
+    match &cmd.out {
+        Some(out) => {
+            if let Err(err) = fs::write(out, serialized) {
+                eprintln!("error: can't write {out}: {err}");
+                process::exit(1);
+            }
+        }
+        None => println!("{serialized}"),
+    }
+}
+
+fn generate_state(cmd: &GenCmd) -> types::State {
+    let mut rng = match cmd.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let living_cells = match cmd.pattern {
+        Pattern::Random => random_cells(cmd, &mut rng),
+        Pattern::Block => (cmd.x..cmd.x + cmd.width)
+            .flat_map(|x| (cmd.y..cmd.y + cmd.height).map(move |y| types::Coordinates { x, y }))
+            .collect(),
+        Pattern::Line => (cmd.x..cmd.x + cmd.width)
+            .map(|x| types::Coordinates { x, y: cmd.y })
+            .collect(),
+        Pattern::Diagonal => CellSet::from_unsorted(vec![
+            types::Coordinates { x: cmd.x, y: cmd.y },
             types::Coordinates {
-                x: rng.gen_range(0..max_x),
-                y: rng.gen_range(0..max_y),
+                x: cmd.x + 1,
+                y: cmd.y + 1,
             },
-            false,
-        );
+        ]),
+        Pattern::Superposition => {
+            // Identical configurations would interfere, so only the distinct ones are kept,
+            // in the order they are generated
+            let mut distinct = HashSet::new();
+            let configurations: Vec<CellSet> = (0..cmd.configurations)
+                .map(|_| random_cells(cmd, &mut rng))
+                .filter(|living_cells| distinct.insert(living_cells.clone()))
+                .collect();
+
+            // The probabilities of the configurations sum to 1
+            let amplitude = Complex::new(1. / (configurations.len() as f64).sqrt(), 0.);
+            return configurations
+                .into_iter()
+                .map(|living_cells| types::Configuration {
+                    amplitude,
+                    living_cells,
+                })
+                .collect();
+        }
+    };
+
+    vec![types::Configuration {
+        //The module of the amplitude should be equal to 1
+        amplitude: Complex::new(1., 0.),
+        living_cells,
+    }]
+}
+
+// Generate a set of living cells randomly placed in the grid
+fn random_cells(cmd: &GenCmd, rng: &mut StdRng) -> CellSet {
+    let mut living_cells = CellSet::new();
+    if cmd.width <= 0 || cmd.height <= 0 {
+        return living_cells;
+    }
+
+    if let Some(density) = cmd.density {
+        for x in cmd.x..cmd.x + cmd.width {
+            for y in cmd.y..cmd.y + cmd.height {
+                if rng.gen_bool(density) {
+                    living_cells.insert(types::Coordinates { x, y });
+                }
+            }
+        }
+        return living_cells;
+    }
+
+    //Get the number of cells we would like to have on the map
+    //it can't be greater than the number of cells of the grid
+    let grid_size = cmd.width as usize * cmd.height as usize;
+    let number_of_cells = cmd
+        .cells
+        .unwrap_or_else(|| rng.gen_range(0..100))
+        .min(grid_size);
+    while living_cells.len() < number_of_cells {
+        living_cells.insert(types::Coordinates {
+            x: rng.gen_range(cmd.x..cmd.x + cmd.width),
+            y: rng.gen_range(cmd.y..cmd.y + cmd.height),
+        });
     }
 
-    state.push(configuration);
-    let serialized_state = serde_json::to_string(&state).unwrap();
-    println!("{serialized_state}");
+    living_cells
 }
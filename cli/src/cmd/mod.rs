@@ -1,5 +1,8 @@
+pub mod compare;
+pub mod density_matrix;
 pub mod gen;
 pub mod run;
+pub mod trajectories;
 
 use clap::{Parser, Subcommand};
 
@@ -18,6 +21,15 @@ enum Commands {
 
     /// run the quantum cellular automata
     Run(run::RunCmd),
+
+    /// compute the reduced density matrix of a few cells
+    DensityMatrix(density_matrix::DensityMatrixCmd),
+
+    /// compare the states of two universes
+    Compare(compare::CompareCmd),
+
+    /// run Monte Carlo trajectories with random measures and average the cells occupations
+    Trajectories(trajectories::TrajectoriesCmd),
 }
 
 pub fn run() {
@@ -30,5 +42,14 @@ pub fn run() {
         Commands::Run(cmd) => {
             run::run(cmd);
         }
+        Commands::DensityMatrix(cmd) => {
+            density_matrix::density_matrix(cmd);
+        }
+        Commands::Compare(cmd) => {
+            compare::compare(cmd);
+        }
+        Commands::Trajectories(cmd) => {
+            trajectories::trajectories(cmd);
+        }
     }
 }
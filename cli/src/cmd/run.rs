@@ -1,12 +1,326 @@
-use clap::Args;
+use clap::{Args, ValueEnum};
+use core::universe::entanglement::Entropies;
+use core::universe::files;
+use core::universe::observables;
+use core::universe::pruning::{PruningPolicy, PruningStrategy};
+use core::universe::step::StepReport;
+use core::universe::topology::{Boundary, Topology};
+use core::universe::types::{LoadOptions, State, Universe};
+use serde::Serialize;
+use std::fs;
+use std::process;
 
 #[derive(Args, Debug)]
 pub struct RunCmd {
-    // the starting state file
+    /// the starting state file, or a snapshot file saved with --save to resume a run
     #[clap(value_name = "STATE_FILE", index = 1)]
-    state_file: Option<String>,
+    state_file: String,
+
+    /// the rules file (JSON or TOML), the default rules (or the rules of the snapshot)
+    /// are used if not provided
+    #[clap(short, long, value_parser)]
+    rules: Option<String>,
+
+    /// normalize the amplitudes of the state so that its total probability is 1
+    #[clap(long, value_parser)]
+    normalize: bool,
+
+    /// number of steps to compute
+    #[clap(short, long, value_parser, default_value_t = 1)]
+    steps: usize,
+
+    /// measure the universe after a step if it has more superposed configurations than this number
+    #[clap(short, long, value_parser)]
+    measure_max: Option<usize>,
+
+    /// seed of the random number generator used by the measures, for reproducible runs
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
+
+    /// store the final state and the steps summary in a Json file instead of printing them
+    #[clap(short, long, value_parser)]
+    out: Option<String>,
+
+    /// save a snapshot of the final universe in a Json file, to resume the run later
+    #[clap(long, value_parser)]
+    save: Option<String>,
+
+    /// record the branching history of the configurations and write it in a file,
+    /// as a Graphviz DOT graph if its extension is .dot and as Json otherwise
+    #[clap(long, value_parser)]
+    history: Option<String>,
+
+    /// topology of the universe, an infinite plane by default
+    /// (or the topology of the snapshot)
+    #[clap(long, value_enum)]
+    topology: Option<TopologyKind>,
+
+    /// width of a rectangle or torus universe
+    #[clap(long, value_parser, required_if_eq_any(&[("topology", "rectangle"), ("topology", "torus")]))]
+    width: Option<i32>,
+
+    /// height of a rectangle or torus universe
+    #[clap(long, value_parser, required_if_eq_any(&[("topology", "rectangle"), ("topology", "torus")]))]
+    height: Option<i32>,
+
+    /// behavior of the borders of a rectangle universe
+    #[clap(long, value_enum, default_value_t = BoundaryKind::Reflecting)]
+    boundary: BoundaryKind,
+
+    /// strategy used to discard the configurations after each step, configurations
+    /// with a probability below 1e-6 are discarded by default (or the policy of the snapshot)
+    #[clap(long, value_enum, requires = "pruning-value")]
+    pruning: Option<PruningKind>,
+
+    /// parameter of the pruning strategy: the threshold, the number of kept configurations
+    /// or the kept fraction of the total probability
+    #[clap(long, value_parser)]
+    pruning_value: Option<f64>,
+
+    /// scale the amplitudes after pruning to keep the total probability unchanged
+    #[clap(long, value_parser)]
+    renormalize: bool,
+
+    /// stop with an error if the total probability drifts by more than this tolerance
+    /// during a step (the pruned probability is not counted as a drift),
+    /// the tolerance of the snapshot is used if not provided
+    #[clap(long, value_parser, value_name = "TOLERANCE")]
+    strict: Option<f64>,
+
+    /// add to the steps summary the entanglement entropies between the cells
+    /// whose x coordinate is below this value and the other cells
+    #[clap(long, value_parser, value_name = "X")]
+    entanglement_split: Option<i32>,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum TopologyKind {
+    /// infinite plane
+    Plane,
+    /// rectangle of the given width and height
+    Rectangle,
+    /// torus of the given even width and height
+    Torus,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum PruningKind {
+    /// discard the configurations whose probability is below the value
+    Absolute,
+    /// discard the configurations whose probability is below the value
+    /// times the highest probability
+    Relative,
+    /// keep only the given number of most probable configurations
+    TopK,
+    /// keep the most probable configurations until their cumulative probability
+    /// reaches the given fraction of the total probability
+    CumulativeProbability,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum BoundaryKind {
+    /// the cells brought outside the rectangle disappear
+    Absorbing,
+    /// the cells of the squares crossing the border stay where they are
+    Reflecting,
+}
+
+// Summary of the universe after a step, the step 0 is the starting state
+#[derive(Serialize, Debug)]
+struct StepSummary {
+    step: usize,
+    configurations: usize,
+    total_probability: f64,
+    living_cells_expectation: f64,
+    center_of_mass: Option<(f64, f64)>,
+    spread: Option<f64>,
+    splits: usize,
+    merges: usize,
+    pruned_configurations: usize,
+    pruned_probability: f64,
+    probability_drift: f64,
+    entanglement: Option<Entropies>,
+    measured: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct RunOutput<'a> {
+    steps: Vec<StepSummary>,
+    state: &'a State,
 }
 
 pub fn run(args: &RunCmd) {
-    println!("Running with state file: {:?}", args.state_file);
+    let options = LoadOptions {
+        normalize: args.normalize,
+    };
+    let universe = match &args.rules {
+        Some(_) => Universe::new_from_files_with(&args.state_file, args.rules.as_deref(), options),
+        None => Universe::load_with(&args.state_file, options),
+    };
+    let mut universe = match universe {
+        Ok(universe) => universe,
+        Err(err) => {
+            eprintln!("error: can't load universe from {}: {err}", args.state_file);
+            process::exit(1);
+        }
+    };
+    if let Err(warning) = universe.validate_normalization() {
+        eprintln!("warning: {warning}, use --normalize to normalize the state");
+    }
+    if let Some(seed) = args.seed {
+        universe = universe.with_seed(seed);
+    }
+    if let Some(topology) = topology(args) {
+        universe = match universe.with_topology(topology) {
+            Ok(universe) => universe,
+            Err(err) => {
+                eprintln!("error: can't set the topology of the universe: {err}");
+                process::exit(1);
+            }
+        };
+    }
+    if let Some(pruning) = pruning(args) {
+        universe = match universe.with_pruning(pruning) {
+            Ok(universe) => universe,
+            Err(err) => {
+                eprintln!("error: can't set the pruning policy of the universe: {err}");
+                process::exit(1);
+            }
+        };
+    } else if args.renormalize {
+        universe.pruning.renormalize = true;
+    }
+    if let Some(tolerance) = args.strict {
+        universe = universe.with_strict_mode(tolerance);
+    }
+    if args.history.is_some() {
+        universe = universe.with_history();
+    }
+
+    let mut steps = vec![summarize(&universe, args, StepReport::default(), false)];
+    for _ in 0..args.steps {
+        let report = match universe.step() {
+            Ok(report) => report,
+            Err(err) => {
+                eprintln!("error: can't compute step {}: {err}", universe.step_count);
+                process::exit(1);
+            }
+        };
+
+        let measured = match args.measure_max {
+            Some(measure_max) if universe.state.len() > measure_max => {
+                if let Err(err) = universe.measure() {
+                    eprintln!("error: can't measure step {}: {err}", universe.step_count);
+                    process::exit(1);
+                }
+                true
+            }
+            _ => false,
+        };
+        steps.push(summarize(&universe, args, report, measured));
+    }
+
+    if let Some(save) = &args.save {
+        if let Err(err) = universe.save(save) {
+            eprintln!("error: can't save snapshot in {save}: {err}");
+            process::exit(1);
+        }
+    }
+
+    if let (Some(history_file), Some(history)) = (&args.history, &universe.history) {
+        if let Err(err) = files::write_history_to_file(history_file, history) {
+            eprintln!("error: can't write history in {history_file}: {err}");
+            process::exit(1);
+        }
+    }
+
+    let output = RunOutput {
+        steps,
+        state: &universe.state,
+    };
+    let serialized_output = serde_json::to_string(&output).unwrap();
+
+    match &args.out {
+        Some(out) => {
+            if let Err(err) = fs::write(out, serialized_output) {
+                eprintln!("error: can't write {out}: {err}");
+                process::exit(1);
+            }
+        }
+        None => println!("{serialized_output}"),
+    }
+}
+
+fn topology(args: &RunCmd) -> Option<Topology> {
+    let width = args.width.unwrap_or_default();
+    let height = args.height.unwrap_or_default();
+    match args.topology.as_ref()? {
+        TopologyKind::Plane => Some(Topology::Plane),
+        TopologyKind::Rectangle => Some(Topology::Rectangle {
+            width,
+            height,
+            boundary: match args.boundary {
+                BoundaryKind::Absorbing => Boundary::Absorbing,
+                BoundaryKind::Reflecting => Boundary::Reflecting,
+            },
+        }),
+        TopologyKind::Torus => Some(Topology::Torus { width, height }),
+    }
+}
+
+fn pruning(args: &RunCmd) -> Option<PruningPolicy> {
+    let value = args.pruning_value.unwrap_or_default();
+    let strategy = match args.pruning.as_ref()? {
+        PruningKind::Absolute => PruningStrategy::Absolute { threshold: value },
+        PruningKind::Relative => PruningStrategy::Relative { threshold: value },
+        PruningKind::TopK => PruningStrategy::TopK {
+            k: value.max(0.) as usize,
+        },
+        PruningKind::CumulativeProbability => {
+            PruningStrategy::CumulativeProbability { probability: value }
+        }
+    };
+    Some(PruningPolicy {
+        strategy,
+        renormalize: args.renormalize,
+    })
+}
+
+fn summarize(
+    universe: &Universe,
+    args: &RunCmd,
+    report: StepReport,
+    measured: bool,
+) -> StepSummary {
+    let moments = observables::population_moments(&universe.state);
+    let entanglement = args.entanglement_split.map(|split| {
+        match universe.entanglement_entropy(|cell| cell.x < split) {
+            Ok(entropies) => entropies,
+            Err(err) => {
+                eprintln!(
+                    "error: can't compute the entanglement entropy of step {}: {err}",
+                    universe.step_count
+                );
+                process::exit(1);
+            }
+        }
+    });
+
+    // The expected number of living cells is the sum of the probabilities
+    // of each cell being alive, which is the sum of the combined state
+    StepSummary {
+        step: universe.step_count,
+        configurations: universe.state.len(),
+        total_probability: universe.total_probability(),
+        living_cells_expectation: universe.combined_state.values().sum(),
+        center_of_mass: moments.map(|moments| moments.center_of_mass),
+        spread: moments.map(|moments| moments.spread),
+        splits: report.splits,
+        merges: report.merges,
+        pruned_configurations: report.pruned.configurations,
+        pruned_probability: report.pruned.probability,
+        probability_drift: report.drift,
+        entanglement,
+        measured,
+    }
 }
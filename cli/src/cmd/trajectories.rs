@@ -0,0 +1,104 @@
+use clap::Args;
+use core::universe::trajectories::{TrajectoryMeasurement, TrajectoryOptions};
+use core::universe::types::{Coordinates, Universe};
+use std::fs;
+use std::process;
+
+#[derive(Args, Debug)]
+pub struct TrajectoriesCmd {
+    /// the starting state file, or a snapshot file saved with run --save
+    #[clap(value_name = "STATE_FILE", index = 1)]
+    state_file: String,
+
+    /// the rules file (JSON or TOML), the default rules (or the rules of the snapshot)
+    /// are used if not provided
+    #[clap(short, long, value_parser)]
+    rules: Option<String>,
+
+    /// number of independent trajectories
+    #[clap(short, long, value_parser, default_value_t = 100)]
+    trajectories: usize,
+
+    /// number of steps of each trajectory
+    #[clap(short, long, value_parser, default_value_t = 1)]
+    steps: usize,
+
+    /// probability of each measure after each step
+    #[clap(long, value_parser, default_value_t = 0.1)]
+    rate: f64,
+
+    /// a rectangle region measured independently of the other regions, as x,y,width,height,
+    /// the whole universe is measured if no region is given
+    #[clap(long = "region", value_name = "X,Y,WIDTH,HEIGHT", value_parser = parse_region, allow_hyphen_values = true)]
+    regions: Vec<Vec<Coordinates>>,
+
+    /// seed of the random number generator of the trajectories, for reproducible runs
+    #[clap(long, value_parser, default_value_t = 0)]
+    seed: u64,
+
+    /// store the statistics in a Json file instead of printing them
+    #[clap(short, long, value_parser)]
+    out: Option<String>,
+}
+
+fn parse_region(value: &str) -> Result<Vec<Coordinates>, String> {
+    let numbers = value
+        .split(',')
+        .map(|number| {
+            number
+                .trim()
+                .parse::<i32>()
+                .map_err(|err| format!("invalid number {number}: {err}"))
+        })
+        .collect::<Result<Vec<i32>, String>>()?;
+    let [x, y, width, height] = numbers[..] else {
+        return Err(format!("{value} is not a x,y,width,height region"));
+    };
+    Ok((y..y + height)
+        .flat_map(|y| (x..x + width).map(move |x| Coordinates { x, y }))
+        .collect())
+}
+
+pub fn trajectories(args: &TrajectoriesCmd) {
+    let universe = match &args.rules {
+        Some(_) => Universe::new_from_files(&args.state_file, args.rules.as_deref()),
+        None => Universe::load(&args.state_file),
+    };
+    let universe = match universe {
+        Ok(universe) => universe,
+        Err(err) => {
+            eprintln!("error: can't load universe from {}: {err}", args.state_file);
+            process::exit(1);
+        }
+    };
+
+    let options = TrajectoryOptions {
+        trajectories: args.trajectories,
+        steps: args.steps,
+        measurement: if args.regions.is_empty() {
+            TrajectoryMeasurement::Global
+        } else {
+            TrajectoryMeasurement::Regions(args.regions.clone())
+        },
+        rate: args.rate,
+        seed: args.seed,
+    };
+    let statistics = match universe.run_trajectories(&options) {
+        Ok(statistics) => statistics,
+        Err(err) => {
+            eprintln!("error: can't run the trajectories: {err}");
+            process::exit(1);
+        }
+    };
+    let serialized = serde_json::to_string(&statistics).unwrap();
+
+    match &args.out {
+        Some(out) => {
+            if let Err(err) = fs::write(out, serialized) {
+                eprintln!("error: can't write {out}: {err}");
+                process::exit(1);
+            }
+        }
+        None => println!("{serialized}"),
+    }
+}
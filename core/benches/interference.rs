@@ -0,0 +1,55 @@
+use core::universe::cell_set::CellSet;
+use core::universe::living_cells::LivingCells;
+use core::universe::tiled_cells::TiledCells;
+use core::universe::types::Universe;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+// Universe of state_grid_20.json after a few steps,
+// when it contains a large superposition of configurations
+fn grid_20_universe<C: LivingCells>(steps: usize) -> Universe<C> {
+    let mut universe = Universe::new_from_files("fixtures/state_grid_20.json", None)
+        .unwrap()
+        .into_storage::<C>();
+    for _ in 0..steps {
+        universe.step().unwrap();
+    }
+    universe
+}
+
+fn bench_step_storage<C: LivingCells>(c: &mut Criterion, name: &str) {
+    let universe = grid_20_universe::<C>(16);
+    c.bench_function(name, |b| {
+        b.iter_batched(
+            || universe.clone(),
+            |mut universe| universe.step().unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_solve_interference_storage<C: LivingCells>(c: &mut Criterion, name: &str) {
+    // Every configuration of the state appears twice and interferes with its copy
+    let mut universe = grid_20_universe::<C>(16);
+    let state = universe.state.clone();
+    universe.state.extend(state);
+    c.bench_function(name, |b| {
+        b.iter_batched(
+            || universe.clone(),
+            |mut universe| universe.solve_interference().unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_step(c: &mut Criterion) {
+    bench_step_storage::<CellSet>(c, "step grid_20");
+    bench_step_storage::<TiledCells>(c, "step grid_20 tiled");
+}
+
+fn bench_solve_interference(c: &mut Criterion) {
+    bench_solve_interference_storage::<CellSet>(c, "solve_interference grid_20");
+    bench_solve_interference_storage::<TiledCells>(c, "solve_interference grid_20 tiled");
+}
+
+criterion_group!(benches, bench_step, bench_solve_interference);
+criterion_main!(benches);
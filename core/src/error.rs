@@ -0,0 +1,246 @@
+use std::fmt;
+use std::io;
+
+use crate::universe::rules::RulesError;
+
+// Error is the error returned by the public functions of the core crate
+#[derive(Debug)]
+pub enum Error {
+    // Reading or writing a file failed
+    Io(io::Error),
+    // The content of a state, rules or snapshot file can't be parsed,
+    // line and column start at 1 and are 0 when the position is unknown
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    // The rules are not a valid unitary matrix, or a rules file contains no rules
+    InvalidRules(RulesError),
+    // The amplitudes of the state can't be used as probabilities
+    InvalidAmplitudes(AmplitudesError),
+    // The topology is invalid or some living cells are outside of it
+    InvalidTopology(String),
+    // A parameter of the pruning policy is out of range
+    InvalidPruningPolicy(String),
+    // The cells of a reduced density matrix are too many or repeated
+    InvalidCells(String),
+    // An option of the Monte Carlo trajectories is out of range
+    InvalidTrajectoryOptions(String),
+    // The total probability drifted by more than the tolerance of the strict mode
+    // during the given step
+    ProbabilityDrift {
+        step: usize,
+        drift: f64,
+        tolerance: f64,
+    },
+    // The snapshot was written by a newer version of the crate
+    UnsupportedSnapshotVersion {
+        version: u32,
+        supported: u32,
+    },
+    // An invariant of the universe is broken, this is a bug of the crate
+    Internal(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AmplitudesError {
+    // The amplitude of the configuration at the given index is NaN or infinite
+    NonFinite { configuration: usize },
+    // The sum of the probabilities of the configurations is 0
+    ZeroTotalProbability,
+    // The sum of the probabilities of the configurations is not 1,
+    // this is only a warning (see `Universe::validate_normalization`)
+    NotNormalized { total_probability: f64 },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{err}"),
+            Error::Parse {
+                line,
+                column,
+                message,
+            } => {
+                if *line == 0 {
+                    write!(f, "parse error: {message}")
+                } else {
+                    write!(
+                        f,
+                        "parse error at line {line}, column {column}: {message}"
+                    )
+                }
+            }
+            Error::InvalidRules(err) => write!(f, "invalid rules: {err}"),
+            Error::InvalidAmplitudes(err) => write!(f, "invalid amplitudes: {err}"),
+            Error::InvalidTopology(message) => write!(f, "invalid topology: {message}"),
+            Error::InvalidPruningPolicy(message) => write!(f, "invalid pruning policy: {message}"),
+            Error::InvalidCells(message) => write!(f, "invalid cells: {message}"),
+            Error::InvalidTrajectoryOptions(message) => {
+                write!(f, "invalid trajectory options: {message}")
+            }
+            Error::ProbabilityDrift {
+                step,
+                drift,
+                tolerance,
+            } => write!(
+                f,
+                "the total probability drifted by {drift:e} during step {step} (tolerance is {tolerance:e})"
+            ),
+            Error::UnsupportedSnapshotVersion { version, supported } => write!(
+                f,
+                "snapshot version {version} is not supported (latest supported version is {supported})"
+            ),
+            Error::Internal(message) => write!(f, "internal error: {message}"),
+        }
+    }
+}
+
+impl fmt::Display for AmplitudesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmplitudesError::NonFinite { configuration } => write!(
+                f,
+                "the amplitude of the configuration {configuration} is not finite"
+            ),
+            AmplitudesError::ZeroTotalProbability => {
+                write!(f, "the total probability of the configurations is 0")
+            }
+            AmplitudesError::NotNormalized { total_probability } => write!(
+                f,
+                "the total probability of the configurations is {total_probability} instead of 1"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::InvalidRules(err) => Some(err),
+            Error::InvalidAmplitudes(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for AmplitudesError {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        if err.is_io() {
+            return Error::Io(err.into());
+        }
+
+        // The message of a serde_json error ends with its position,
+        // which is already given by the line and column
+        let message = err.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(position) => message[..position].to_string(),
+            None => message,
+        };
+        Error::Parse {
+            line: err.line(),
+            column: err.column(),
+            message,
+        }
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        // The position of a toml error starts at 0
+        let (line, column) = match err.line_col() {
+            Some((line, column)) => (line + 1, column + 1),
+            None => (0, 0),
+        };
+        Error::Parse {
+            line,
+            column,
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<RulesError> for Error {
+    fn from(err: RulesError) -> Self {
+        Error::InvalidRules(err)
+    }
+}
+
+impl From<AmplitudesError> for Error {
+    fn from(err: AmplitudesError) -> Self {
+        Error::InvalidAmplitudes(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::rules::{self, RulesError};
+    use crate::universe::types::Universe;
+    use crate::{AmplitudesError, Error};
+
+    #[test]
+    fn test_error_kinds() {
+        struct Test {
+            got: Result<Universe, Error>,
+            exp: fn(&Error) -> bool,
+        }
+
+        let tests = [
+            Test {
+                got: Universe::new_from_files("fixtures/does_not_exist.json", None),
+                exp: |err| matches!(err, Error::Io(_)),
+            },
+            Test {
+                got: Universe::new_from_str("[\n  {\"amplitude\": }\n]"),
+                exp: |err| matches!(err, Error::Parse { line: 2, .. }),
+            },
+            Test {
+                got: Universe::new_from_str(
+                    r#"[{"amplitude": {"re": 0, "im": 0}, "living_cells": []}]"#,
+                ),
+                exp: |err| {
+                    matches!(
+                        err,
+                        Error::InvalidAmplitudes(AmplitudesError::ZeroTotalProbability)
+                    )
+                },
+            },
+            Test {
+                got: Universe::new_from_state(Vec::new(), vec![rules::get_default_rules()]),
+                exp: |err| {
+                    matches!(
+                        err,
+                        Error::InvalidAmplitudes(AmplitudesError::ZeroTotalProbability)
+                    )
+                },
+            },
+            Test {
+                got: Universe::new_from_state(Universe::new().state, Vec::new()),
+                exp: |err| matches!(err, Error::InvalidRules(RulesError::Empty)),
+            },
+        ];
+
+        for t in tests {
+            match t.got {
+                Err(err) => assert!((t.exp)(&err), "unexpected error: {err:?}"),
+                Ok(_) => panic!("an error was expected"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_toml_parse_position() {
+        let got = rules::rules_from_toml_str("rules = [\n  [{re = 1, im = }]\n]");
+        assert!(matches!(got, Err(Error::Parse { line: 2, .. })));
+    }
+}
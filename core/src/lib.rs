@@ -1 +1,4 @@
+pub mod error;
 pub mod universe;
+
+pub use error::{AmplitudesError, Error};
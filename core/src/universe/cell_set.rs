@@ -0,0 +1,216 @@
+use serde::de::{Deserializer, IgnoredAny};
+use serde::ser::{SerializeSeq, Serializer};
+use serde::{Deserialize, Serialize};
+use std::slice;
+
+use super::living_cells::{self, LivingCells};
+use super::types::Coordinates;
+
+// CellSet is the set of the living cells of a configuration
+//
+// The cells are stored in a vector sorted by coordinates, so two configurations
+// with the same living cells have the same CellSet and iterating on the cells
+// always gives them in the same order
+//
+// A CellSet is serialized as a list of [x, y] pairs:
+// "living_cells": [[0, 0], [1, 1]]
+//
+// The legacy format, in which each cell was a pair of coordinates and a boolean,
+// is still accepted when deserializing:
+// "living_cells": [[{"x": 0, "y": 0}, false], [{"x": 1, "y": 1}, false]]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CellSet {
+    cells: Vec<Coordinates>,
+}
+
+impl CellSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // from_unsorted creates a CellSet from cells in any order,
+    // it is faster than inserting the cells one by one
+    pub fn from_unsorted(mut cells: Vec<Coordinates>) -> Self {
+        cells.sort_unstable();
+        cells.dedup();
+        Self { cells }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn contains(&self, coordinates: &Coordinates) -> bool {
+        self.cells.binary_search(coordinates).is_ok()
+    }
+
+    // insert returns false if the cell was already in the set
+    pub fn insert(&mut self, coordinates: Coordinates) -> bool {
+        match self.cells.binary_search(&coordinates) {
+            Ok(_) => false,
+            Err(index) => {
+                self.cells.insert(index, coordinates);
+                true
+            }
+        }
+    }
+
+    // remove returns false if the cell was not in the set
+    pub fn remove(&mut self, coordinates: &Coordinates) -> bool {
+        match self.cells.binary_search(coordinates) {
+            Ok(index) => {
+                self.cells.remove(index);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Coordinates> {
+        self.cells.iter()
+    }
+
+    pub fn as_slice(&self) -> &[Coordinates] {
+        &self.cells
+    }
+}
+
+impl LivingCells for CellSet {
+    fn from_unsorted(cells: Vec<Coordinates>) -> Self {
+        CellSet::from_unsorted(cells)
+    }
+
+    fn len(&self) -> usize {
+        CellSet::len(self)
+    }
+
+    fn contains(&self, coordinates: &Coordinates) -> bool {
+        CellSet::contains(self, coordinates)
+    }
+
+    fn insert(&mut self, coordinates: Coordinates) -> bool {
+        CellSet::insert(self, coordinates)
+    }
+
+    fn remove(&mut self, coordinates: &Coordinates) -> bool {
+        CellSet::remove(self, coordinates)
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = Coordinates> + '_> {
+        Box::new(self.cells.iter().cloned())
+    }
+
+    // The hash is the sum of the hashes of the cells
+    fn interference_hash(&self) -> u64 {
+        self.cells.iter().fold(0, |hash, coordinates| {
+            hash.wrapping_add(living_cells::mix_hash(living_cells::pack_pair(
+                coordinates.x,
+                coordinates.y,
+            )))
+        })
+    }
+}
+
+impl FromIterator<Coordinates> for CellSet {
+    fn from_iter<I: IntoIterator<Item = Coordinates>>(iter: I) -> Self {
+        Self::from_unsorted(iter.into_iter().collect())
+    }
+}
+
+impl<'a> IntoIterator for &'a CellSet {
+    type Item = &'a Coordinates;
+    type IntoIter = slice::Iter<'a, Coordinates>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cells.iter()
+    }
+}
+
+impl Serialize for CellSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.cells.len()))?;
+        for coordinates in self.cells.iter() {
+            seq.serialize_element(&(coordinates.x, coordinates.y))?;
+        }
+        seq.end()
+    }
+}
+
+// A serialized cell, either a [x, y] pair or a legacy [{"x": x, "y": y}, bool] pair
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CellEntry {
+    Pair(i32, i32),
+    Legacy(Coordinates, IgnoredAny),
+}
+
+impl<'de> Deserialize<'de> for CellSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries: Vec<CellEntry> = Vec::deserialize(deserializer)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| match entry {
+                CellEntry::Pair(x, y) => Coordinates { x, y },
+                CellEntry::Legacy(coordinates, _) => coordinates,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::cell_set::CellSet;
+    use crate::universe::types::Coordinates;
+
+    #[test]
+    fn test_cell_set() {
+        let c = |x, y| Coordinates { x, y };
+        let mut cells = CellSet::from_unsorted(vec![c(1, 0), c(-2, 5), c(1, 0), c(0, 3)]);
+        assert_eq!(cells.as_slice(), &[c(-2, 5), c(0, 3), c(1, 0)]);
+
+        assert!(cells.insert(c(0, -1)));
+        assert!(!cells.insert(c(0, 3)));
+        assert!(cells.contains(&c(0, -1)));
+        assert!(cells.remove(&c(-2, 5)));
+        assert!(!cells.remove(&c(-2, 5)));
+        assert!(!cells.contains(&c(-2, 5)));
+        assert_eq!(cells.as_slice(), &[c(0, -1), c(0, 3), c(1, 0)]);
+    }
+
+    #[test]
+    fn test_cell_set_serde() {
+        struct Test {
+            content: &'static str,
+            exp: Vec<(i32, i32)>,
+        }
+
+        let tests = [
+            Test {
+                content: "[[1, 2], [0, -3]]",
+                exp: vec![(0, -3), (1, 2)],
+            },
+            Test {
+                content: r#"[[{"x": 1, "y": 2}, false], [{"x": 0, "y": -3}, true]]"#,
+                exp: vec![(0, -3), (1, 2)],
+            },
+            Test {
+                content: "[]",
+                exp: vec![],
+            },
+        ];
+
+        for t in tests {
+            let cells: CellSet = serde_json::from_str(t.content).unwrap();
+            let got: Vec<(i32, i32)> = cells.iter().map(|c| (c.x, c.y)).collect();
+            assert_eq!(got, t.exp);
+
+            let serialized = serde_json::to_string(&cells).unwrap();
+            let exp: Vec<[i32; 2]> = t.exp.iter().map(|(x, y)| [*x, *y]).collect();
+            assert_eq!(serialized, serde_json::to_string(&exp).unwrap());
+        }
+    }
+}
@@ -1,6 +1,7 @@
+use super::living_cells::LivingCells;
 use super::types::*;
 
-impl Universe {
+impl<C: LivingCells> Universe<C> {
     // This function computes the combined state
     // of a Universe from its state
     //
@@ -22,7 +23,12 @@ impl Universe {
         for configuration in self.state.iter() {
             let probability = configuration.amplitude.norm_sqr();
 
-            for coordinates in configuration.living_cells.keys() {
+            // The cells of a configuration without probability are not alive
+            if probability == 0. {
+                continue;
+            }
+
+            for coordinates in configuration.living_cells.cells() {
                 *self
                     .combined_state
                     .entry(Coordinates {
@@ -0,0 +1,164 @@
+use num::complex::Complex;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+use super::living_cells::LivingCells;
+use super::observables;
+use super::types::*;
+
+// Comparison of two universes, see `Universe::compare`
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Comparison {
+    #[serde(with = "ComplexDef")]
+    pub inner_product: Complex<f64>,
+    pub fidelity: f64,
+    pub trace_distance: f64,
+    // Configurations of a universe whose living cells are in no configuration
+    // of the other universe, with their total probability
+    pub only_in_self: usize,
+    pub only_in_self_probability: f64,
+    pub only_in_other: usize,
+    pub only_in_other_probability: f64,
+}
+
+impl<C: LivingCells> Universe<C> {
+    // inner_product returns ⟨self|other⟩, the sum over the configurations with the same
+    // living cells in both universes of the conjugate of the amplitude in self
+    // times the amplitude in other
+    //
+    // The parity of the steps of the universes is not compared
+    pub fn inner_product(&self, other: &Universe<C>) -> Complex<f64> {
+        let other_amplitudes = amplitudes_by_living_cells(&other.state);
+        self.state
+            .iter()
+            .filter_map(|configuration| {
+                other_amplitudes
+                    .get(&configuration.living_cells)
+                    .map(|amplitude| configuration.amplitude.conj() * amplitude)
+            })
+            .sum()
+    }
+
+    // fidelity returns |⟨self|other⟩|² divided by the total probabilities of the universes,
+    // which is 1 if the universes are in the same state (up to a global phase)
+    // and 0 if they have no configuration in common
+    pub fn fidelity(&self, other: &Universe<C>) -> f64 {
+        let total_probabilities = self.total_probability() * other.total_probability();
+        if total_probabilities <= 0. {
+            return 0.;
+        }
+        self.inner_product(other).norm_sqr() / total_probabilities
+    }
+
+    // trace_distance returns the trace distance between the combined states of the universes,
+    // seen as diagonal matrices: half the sum over the cells of the difference
+    // of their probabilities to be alive in both universes
+    //
+    // The probabilities are divided by the total probability of each universe
+    // (see `observables::occupations`), the distance is 0 if the probability of each cell
+    // to be alive is the same in both universes, even if their states are different
+    pub fn trace_distance(&self, other: &Universe<C>) -> f64 {
+        let occupations = observables::occupations(&self.state);
+        let other_occupations = observables::occupations(&other.state);
+
+        // The cells are sorted for the sum not to depend on the order of the HashMaps
+        let cells: BTreeSet<&Coordinates> =
+            occupations.keys().chain(other_occupations.keys()).collect();
+        cells
+            .into_iter()
+            .map(|cell| {
+                let occupation = occupations.get(cell).unwrap_or(&0.);
+                let other_occupation = other_occupations.get(cell).unwrap_or(&0.);
+                (occupation - other_occupation).abs()
+            })
+            .sum::<f64>()
+            / 2.
+    }
+
+    // compare returns the inner product, the fidelity and the trace distance
+    // of the universes, and their configurations that are not in the other universe
+    pub fn compare(&self, other: &Universe<C>) -> Comparison {
+        let (only_in_self, only_in_self_probability) = unmatched(&self.state, &other.state);
+        let (only_in_other, only_in_other_probability) = unmatched(&other.state, &self.state);
+        Comparison {
+            inner_product: self.inner_product(other),
+            fidelity: self.fidelity(other),
+            trace_distance: self.trace_distance(other),
+            only_in_self,
+            only_in_self_probability,
+            only_in_other,
+            only_in_other_probability,
+        }
+    }
+}
+
+fn amplitudes_by_living_cells<C: LivingCells>(state: &State<C>) -> HashMap<&C, Complex<f64>> {
+    let mut amplitudes: HashMap<&C, Complex<f64>> = HashMap::new();
+    for configuration in state.iter() {
+        *amplitudes
+            .entry(&configuration.living_cells)
+            .or_insert(Complex::new(0., 0.)) += configuration.amplitude;
+    }
+    amplitudes
+}
+
+// unmatched returns the number of configurations of the state whose living cells
+// are in no configuration of the other state, and their total probability
+fn unmatched<C: LivingCells>(state: &State<C>, other: &State<C>) -> (usize, f64) {
+    let other_amplitudes = amplitudes_by_living_cells(other);
+    state
+        .iter()
+        .filter(|configuration| !other_amplitudes.contains_key(&configuration.living_cells))
+        .fold((0, 0.), |(count, probability), configuration| {
+            (count + 1, probability + configuration.amplitude.norm_sqr())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::types::Universe;
+    use num::complex::Complex;
+
+    #[test]
+    fn test_compare() {
+        let universe = Universe::new_from_str(
+            r#"[
+                {"amplitude": {"re": 0.6, "im": 0}, "living_cells": [[0, 0]]},
+                {"amplitude": {"re": 0, "im": 0.8}, "living_cells": [[1, 0]]}
+            ]"#,
+        )
+        .unwrap();
+        let other = Universe::new_from_str(
+            r#"[
+                {"amplitude": {"re": 0, "im": 0.6}, "living_cells": [[0, 0]]},
+                {"amplitude": {"re": 0.8, "im": 0}, "living_cells": [[2, 0]]}
+            ]"#,
+        )
+        .unwrap();
+
+        let comparison = universe.compare(&other);
+        assert!((comparison.inner_product - Complex::new(0., 0.36)).norm() < 1e-12);
+        assert!((comparison.fidelity - 0.1296).abs() < 1e-12);
+        assert!((comparison.trace_distance - 0.64).abs() < 1e-12);
+        assert_eq!(comparison.only_in_self, 1);
+        assert!((comparison.only_in_self_probability - 0.64).abs() < 1e-12);
+        assert_eq!(comparison.only_in_other, 1);
+        assert!((comparison.only_in_other_probability - 0.64).abs() < 1e-12);
+
+        assert!((universe.fidelity(&universe) - 1.).abs() < 1e-12);
+        assert_eq!(universe.trace_distance(&universe), 0.);
+
+        // A step followed by a step back gives back the same state
+        let start = Universe::new_from_files("fixtures/state_2_diagonal_cells.json", None).unwrap();
+        let mut universe = start.clone();
+        for _ in 0..3 {
+            universe.step().unwrap();
+        }
+        assert!(universe.fidelity(&start) < 1.);
+        for _ in 0..3 {
+            universe.step_back().unwrap();
+        }
+        assert!((universe.fidelity(&start) - 1.).abs() < 1e-9);
+        assert!(universe.trace_distance(&start) < 1e-9);
+    }
+}
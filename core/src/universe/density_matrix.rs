@@ -0,0 +1,202 @@
+use num::complex::Complex;
+use serde::{Serialize, Serializer};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use super::entanglement;
+use super::living_cells::LivingCells;
+use super::types::*;
+use crate::Error;
+
+// Maximum number of cells of a reduced density matrix, its size is 2^n * 2^n
+pub const MAX_DENSITY_MATRIX_CELLS: usize = 10;
+
+// Reduced density matrix of a few cells, the rest of the universe being traced out
+//
+// The basis states are the 2^n patterns of living cells of the n cells:
+// the bit k of the index of a basis state is 1 if cells[k] is alive,
+// e.g. with the cells [a, b] the index 2 is the pattern in which only b is alive
+//
+// The diagonal entries are the probabilities of the patterns, and the off-diagonal
+// entries the coherences between them. It is serialized as:
+// {"cells": [{"x": 0, "y": 0}, {"x": 1, "y": 0}], "matrix": [[{"re": 0.5, "im": 0}, ...], ...]}
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DensityMatrix {
+    pub cells: Vec<Coordinates>,
+    #[serde(serialize_with = "serialize_matrix")]
+    pub matrix: Vec<Vec<Complex<f64>>>,
+}
+
+fn serialize_matrix<S: Serializer>(
+    matrix: &[Vec<Complex<f64>>],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Entry {
+        re: f64,
+        im: f64,
+    }
+
+    serializer.collect_seq(matrix.iter().map(|row| {
+        row.iter()
+            .map(|entry| Entry {
+                re: entry.re,
+                im: entry.im,
+            })
+            .collect::<Vec<_>>()
+    }))
+}
+
+impl DensityMatrix {
+    // to_csv returns the entries of the matrix as CSV, one line per entry:
+    // row,column,re,im
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("row,column,re,im\n");
+        for (i, row) in self.matrix.iter().enumerate() {
+            for (j, entry) in row.iter().enumerate() {
+                let _ = writeln!(csv, "{i},{j},{},{}", entry.re, entry.im);
+            }
+        }
+        csv
+    }
+}
+
+impl<C: LivingCells> Universe<C> {
+    // reduced_density_matrix returns the density matrix of the given cells,
+    // obtained by tracing out the other cells (see `DensityMatrix`)
+    //
+    // An InvalidCells error is returned if a cell is repeated or if there are
+    // more than MAX_DENSITY_MATRIX_CELLS cells, and an AmplitudesError
+    // if the amplitudes can't be used as probabilities
+    pub fn reduced_density_matrix(&self, cells: &[Coordinates]) -> Result<DensityMatrix, Error> {
+        if cells.len() > MAX_DENSITY_MATRIX_CELLS {
+            return Err(Error::InvalidCells(format!(
+                "{} cells are given, the maximum is {MAX_DENSITY_MATRIX_CELLS}",
+                cells.len()
+            )));
+        }
+        if cells.iter().collect::<HashSet<_>>().len() != cells.len() {
+            return Err(Error::InvalidCells(
+                "a cell is given several times".to_string(),
+            ));
+        }
+        validate_amplitudes(&self.state)?;
+
+        // Each configuration is split in the pattern of the cells
+        // and the living cells outside of them
+        let (patterns, rho) = entanglement::reduced_density_matrix_by(&self.state, |i| {
+            let living_cells = &self.state[i].living_cells;
+            let pattern = cells
+                .iter()
+                .enumerate()
+                .filter(|(_, cell)| living_cells.contains(cell))
+                .fold(0_usize, |pattern, (k, _)| pattern | 1 << k);
+            let rest: Vec<Coordinates> = living_cells
+                .cells()
+                .filter(|cell| !cells.contains(cell))
+                .collect();
+            (pattern, rest)
+        });
+
+        // The patterns that appear in no configuration have no probability
+        let size = 1 << cells.len();
+        let mut matrix = vec![vec![Complex::new(0., 0.); size]; size];
+        for (row, pattern) in rho.into_iter().zip(patterns.iter()) {
+            for (entry, other_pattern) in row.into_iter().zip(patterns.iter()) {
+                matrix[*pattern][*other_pattern] = entry;
+            }
+        }
+
+        Ok(DensityMatrix {
+            cells: cells.to_vec(),
+            matrix,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::types::{Coordinates, Universe};
+    use crate::Error;
+    use num::complex::Complex;
+
+    #[test]
+    fn test_reduced_density_matrix() {
+        let c = |x, y| Coordinates { x, y };
+
+        // The cell (0, 0) is alive in both configurations, and either (1, 0) or (2, 0)
+        // is alive, coherently since the rest of the universe is the same
+        let universe = Universe::new_from_str(
+            r#"[
+                {"amplitude": {"re": 0.6, "im": 0}, "living_cells": [[0, 0], [1, 0], [5, 5]]},
+                {"amplitude": {"re": 0, "im": 0.8}, "living_cells": [[0, 0], [2, 0], [5, 5]]}
+            ]"#,
+        )
+        .unwrap();
+
+        struct Test {
+            cells: Vec<Coordinates>,
+            exp: Vec<(usize, usize, Complex<f64>)>,
+        }
+
+        let tests = [
+            Test {
+                cells: vec![c(1, 0), c(2, 0)],
+                exp: vec![
+                    (1, 1, Complex::new(0.36, 0.)),
+                    (1, 2, Complex::new(0., -0.48)),
+                    (2, 1, Complex::new(0., 0.48)),
+                    (2, 2, Complex::new(0.64, 0.)),
+                ],
+            },
+            // Tracing out (2, 0) removes the coherence
+            Test {
+                cells: vec![c(0, 0), c(1, 0)],
+                exp: vec![
+                    (1, 1, Complex::new(0.64, 0.)),
+                    (3, 3, Complex::new(0.36, 0.)),
+                ],
+            },
+            Test {
+                cells: vec![],
+                exp: vec![(0, 0, Complex::new(1., 0.))],
+            },
+        ];
+
+        for t in tests {
+            let got = universe.reduced_density_matrix(&t.cells).unwrap();
+            let size = 1 << t.cells.len();
+            assert_eq!(got.matrix.len(), size);
+            for i in 0..size {
+                for j in 0..size {
+                    let exp = t
+                        .exp
+                        .iter()
+                        .find(|(row, column, _)| (*row, *column) == (i, j))
+                        .map_or(Complex::new(0., 0.), |(_, _, entry)| *entry);
+                    assert!(
+                        (got.matrix[i][j] - exp).norm() < 1e-12,
+                        "{:?} [{i}][{j}]",
+                        t.cells
+                    );
+                }
+            }
+        }
+
+        let got = universe
+            .reduced_density_matrix(&[c(1, 0), c(2, 0)])
+            .unwrap();
+        assert!(got.to_csv().starts_with("row,column,re,im\n0,0,0,0\n"));
+        assert!(got.to_csv().contains("\n1,2,0,-0.48\n"));
+
+        assert!(matches!(
+            universe.reduced_density_matrix(&[c(0, 0), c(0, 0)]),
+            Err(Error::InvalidCells(_))
+        ));
+        let too_many: Vec<Coordinates> = (0..11).map(|x| c(x, 0)).collect();
+        assert!(matches!(
+            universe.reduced_density_matrix(&too_many),
+            Err(Error::InvalidCells(_))
+        ));
+    }
+}
@@ -0,0 +1,209 @@
+use num::complex::Complex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use super::linear_algebra;
+use super::living_cells::LivingCells;
+use super::types::*;
+use crate::Error;
+
+// Entropies of the reduced density matrix of a region, in bits
+//
+// - von_neumann: -Tr(ρ log2 ρ)
+// - renyi_2: -log2 Tr(ρ²)
+//
+// Both are 0 if the region is not entangled with the rest of the universe,
+// and at most log2 of the number of distinct configurations of the region
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Entropies {
+    pub von_neumann: f64,
+    pub renyi_2: f64,
+}
+
+// Eigenvalues of a density matrix below this value are rounding errors
+const EIGENVALUE_TOLERANCE: f64 = 1e-12;
+
+impl<C: LivingCells> Universe<C> {
+    // entanglement_entropy returns the entropies of the entanglement
+    // between the cells for which region_a returns true and the other cells,
+    // e.g. universe.entanglement_entropy(|cell| cell.x < 10)
+    //
+    // Writing the state as the sum of a_i |A_i⟩|B_i⟩, where A_i and B_i are the living cells
+    // of the configuration i inside and outside of the region, the reduced density matrix
+    // ρ_A = Tr_B |ψ⟩⟨ψ| is computed over the distinct A_i, or ρ_B over the distinct B_i
+    // if there are less of them (both have the same non zero eigenvalues)
+    //
+    // An AmplitudesError is returned if the amplitudes can't be used as probabilities
+    pub fn entanglement_entropy<F: Fn(&Coordinates) -> bool>(
+        &self,
+        region_a: F,
+    ) -> Result<Entropies, Error> {
+        validate_amplitudes(&self.state)?;
+
+        let parts: Vec<(Vec<Coordinates>, Vec<Coordinates>)> = self
+            .state
+            .iter()
+            .map(|configuration| configuration.living_cells.cells().partition(&region_a))
+            .collect();
+        let distinct_a = parts.iter().map(|(a, _)| a).collect::<Vec<_>>();
+        let distinct_b = parts.iter().map(|(_, b)| b).collect::<Vec<_>>();
+        let swap = count_distinct(&distinct_b) < count_distinct(&distinct_a);
+
+        let (_, rho) = reduced_density_matrix_by(&self.state, |i| {
+            let (a, b) = parts[i].clone();
+            if swap {
+                (b, a)
+            } else {
+                (a, b)
+            }
+        });
+        Ok(entropies(&rho))
+    }
+}
+
+fn count_distinct<K: Hash + Eq>(keys: &[K]) -> usize {
+    keys.iter().collect::<HashSet<_>>().len()
+}
+
+// reduced_density_matrix_by returns the reduced density matrix ρ_A of a subsystem A
+// and the distinct parts in A it is indexed by, in the order of the state
+//
+// part(i) returns the part in A of the configuration i and its part in the complement B,
+// the probabilities are divided by the total probability of the state
+pub(crate) fn reduced_density_matrix_by<C, K, B, F>(
+    state: &State<C>,
+    part: F,
+) -> (Vec<K>, Vec<Vec<Complex<f64>>>)
+where
+    K: Hash + Eq + Clone,
+    B: Hash + Eq,
+    F: Fn(usize) -> (K, B),
+{
+    let total_probability: f64 = state
+        .iter()
+        .map(|configuration| configuration.amplitude.norm_sqr())
+        .sum();
+    let scale = if total_probability > 0. {
+        1. / total_probability.sqrt()
+    } else {
+        1.
+    };
+
+    // The configurations are grouped by their part in B, since only the configurations
+    // with the same part in B contribute to the same entries of ρ_A
+    let mut a_indexes: HashMap<K, usize> = HashMap::new();
+    let mut a_parts: Vec<K> = Vec::new();
+    let mut b_indexes: HashMap<B, usize> = HashMap::new();
+    let mut groups: Vec<Vec<(usize, Complex<f64>)>> = Vec::new();
+    for (i, configuration) in state.iter().enumerate() {
+        let (a, b) = part(i);
+        let a_index = *a_indexes.entry(a.clone()).or_insert_with(|| {
+            a_parts.push(a);
+            a_parts.len() - 1
+        });
+        let b_index = *b_indexes.entry(b).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[b_index].push((a_index, configuration.amplitude * scale));
+    }
+
+    // ρ_A[α][α'] is the sum over the parts β in B of ψ(α, β) ψ(α', β)*
+    let mut rho = vec![vec![Complex::new(0., 0.); a_parts.len()]; a_parts.len()];
+    for group in groups.iter() {
+        for (alpha, amplitude) in group.iter() {
+            for (alpha_prime, amplitude_prime) in group.iter() {
+                rho[*alpha][*alpha_prime] += amplitude * amplitude_prime.conj();
+            }
+        }
+    }
+
+    (a_parts, rho)
+}
+
+// entropies returns the entropies of a density matrix
+pub(crate) fn entropies(rho: &[Vec<Complex<f64>>]) -> Entropies {
+    let von_neumann = linear_algebra::hermitian_eigenvalues(rho)
+        .into_iter()
+        .filter(|eigenvalue| *eigenvalue > EIGENVALUE_TOLERANCE)
+        .map(|eigenvalue| -eigenvalue * eigenvalue.log2())
+        .sum::<f64>();
+
+    // Tr(ρ²) is the sum of the squared norms of the entries since ρ is Hermitian
+    let purity: f64 = rho.iter().flatten().map(|entry| entry.norm_sqr()).sum();
+
+    // The rounding errors can make the entropies slightly negative, or -0
+    let non_negative = |entropy: f64| if entropy > 0. { entropy } else { 0. };
+    Entropies {
+        von_neumann: non_negative(von_neumann),
+        renyi_2: non_negative(-purity.log2()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::types::Universe;
+
+    #[test]
+    fn test_entanglement_entropy() {
+        struct Test {
+            content: &'static str,
+            exp_von_neumann: f64,
+            exp_renyi_2: f64,
+        }
+
+        let tests = [
+            // A single configuration is not entangled
+            Test {
+                content: r#"[{"amplitude": {"re": 1, "im": 0}, "living_cells": [[0, 0], [5, 0]]}]"#,
+                exp_von_neumann: 0.,
+                exp_renyi_2: 0.,
+            },
+            // A superposition in the left region only is not entangled
+            Test {
+                content: r#"[
+                    {"amplitude": {"re": 0.6, "im": 0}, "living_cells": [[0, 0], [5, 0]]},
+                    {"amplitude": {"re": 0, "im": 0.8}, "living_cells": [[1, 0], [5, 0]]}
+                ]"#,
+                exp_von_neumann: 0.,
+                exp_renyi_2: 0.,
+            },
+            // A cell alive either on the left or on the right is maximally entangled
+            Test {
+                content: r#"[
+                    {"amplitude": {"re": 1, "im": 0}, "living_cells": [[0, 0]]},
+                    {"amplitude": {"re": 0, "im": -1}, "living_cells": [[5, 0]]}
+                ]"#,
+                exp_von_neumann: 1.,
+                exp_renyi_2: 1.,
+            },
+            // Three configurations with the probabilities 0.5, 0.25 and 0.25,
+            // distinct on both sides
+            Test {
+                content: r#"[
+                    {"amplitude": {"re": 0.7071067811865476, "im": 0}, "living_cells": [[0, 0], [5, 0]]},
+                    {"amplitude": {"re": 0.5, "im": 0}, "living_cells": [[1, 0], [6, 0]]},
+                    {"amplitude": {"re": -0.5, "im": 0}, "living_cells": [[7, 0]]}
+                ]"#,
+                exp_von_neumann: 1.5,
+                exp_renyi_2: -(0.375_f64.log2()),
+            },
+        ];
+
+        for t in tests {
+            let universe = Universe::new_from_str(t.content).unwrap();
+            let got = universe.entanglement_entropy(|cell| cell.x < 3).unwrap();
+            assert!(
+                (got.von_neumann - t.exp_von_neumann).abs() < 1e-9,
+                "{}",
+                t.content
+            );
+            assert!((got.renyi_2 - t.exp_renyi_2).abs() < 1e-9, "{}", t.content);
+
+            // The entropies are symmetric
+            let got_complement = universe.entanglement_entropy(|cell| cell.x >= 3).unwrap();
+            assert!((got_complement.von_neumann - got.von_neumann).abs() < 1e-9);
+        }
+    }
+}
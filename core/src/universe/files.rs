@@ -1,9 +1,59 @@
-use super::types;
+use super::{history, rules, snapshot, types};
+use crate::Error;
 use std::fs;
-use std::io::Error;
+use std::path::Path;
 
 pub fn get_state_from_file(state_file: &str) -> Result<types::State, Error> {
     let content = fs::read_to_string(state_file)?;
     let state: types::State = serde_json::from_str(&content)?;
     Ok(state)
 }
+
+// get_rules_from_file reads a rules file, see `rules::RulesFile` for the format
+// The file is parsed as TOML if its extension is .toml and as JSON otherwise
+pub fn get_rules_from_file(rules_file: &str) -> Result<Vec<types::Rules>, Error> {
+    let content = fs::read_to_string(rules_file)?;
+    match Path::new(rules_file).extension() {
+        Some(extension) if extension == "toml" => rules::rules_from_toml_str(&content),
+        _ => rules::rules_from_json_str(&content),
+    }
+}
+
+// get_snapshot_from_file reads a file containing either a snapshot or a state
+pub fn get_snapshot_from_file(snapshot_file: &str) -> Result<snapshot::SnapshotFile, Error> {
+    let content = fs::read_to_string(snapshot_file)?;
+    snapshot::snapshot_file_from_str(&content)
+}
+
+pub fn write_snapshot_to_file(
+    snapshot_file: &str,
+    snapshot: &snapshot::Snapshot,
+) -> Result<(), Error> {
+    let content = serde_json::to_string(snapshot)?;
+    fs::write(snapshot_file, content)?;
+    Ok(())
+}
+
+// write_history_to_file writes a history as a Graphviz DOT graph
+// if the extension of the file is .dot and as JSON otherwise
+pub fn write_history_to_file(history_file: &str, history: &history::History) -> Result<(), Error> {
+    let content = match Path::new(history_file).extension() {
+        Some(extension) if extension == "dot" => history.to_dot(),
+        _ => serde_json::to_string(history)?,
+    };
+    fs::write(history_file, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::{files, rules};
+
+    #[test]
+    fn test_get_rules_from_file() {
+        let got = files::get_rules_from_file("fixtures/rules_default.json").unwrap();
+        assert_eq!(got, vec![rules::get_default_rules()]);
+
+        assert!(files::get_rules_from_file("fixtures/does_not_exist.json").is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use num::complex::Complex;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+use super::living_cells::LivingCells;
+use super::types::*;
+
+// The history records how the configurations of the universe branch and merge,
+// it is recorded once enabled with `Universe::with_history`
+//
+// Each entry of the history contains the configurations of the state after an event,
+// in the order of the state, each one with the configurations of the previous entry
+// it comes from (its parents):
+// - a configuration split during a step has a single parent, and the top left cells
+//   of the squares in which the parent split are given with each edge
+// - a configuration resulting from interferences has several parents,
+//   one for each merged configuration
+// - the configurations pruned after the interferences have no child
+//
+// The factor of an edge is the amplitude of the child configuration before the interferences
+// divided by the amplitude of the parent, and multiplied by the renormalization of the pruning
+// if any, so that the amplitude of a configuration is the sum of the amplitudes of its parents
+// multiplied by the factors of their edges (the configurations without amplitude
+// contribute nothing to their children and have no edge)
+//
+// The history is a directed acyclic graph that can be exported as JSON
+// or as a Graphviz DOT graph (see `History::to_dot`)
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryEvent {
+    // The history started to be recorded
+    Start,
+    Step,
+    StepBack,
+    // A measure of the whole universe or of a region,
+    // each kept configuration has a single parent
+    Measure,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub event: HistoryEvent,
+    pub step_count: usize,
+    pub configurations: Vec<HistoryNode>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HistoryNode {
+    #[serde(with = "ComplexDef")]
+    pub amplitude: Complex<f64>,
+    pub living_cells: usize,
+    pub parents: Vec<HistoryEdge>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HistoryEdge {
+    // Index of the parent configuration in the previous entry
+    pub parent: usize,
+    #[serde(with = "ComplexDef")]
+    pub factor: Complex<f64>,
+    pub split_squares: Vec<Coordinates>,
+}
+
+impl HistoryEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HistoryEvent::Start => "start",
+            HistoryEvent::Step => "step",
+            HistoryEvent::StepBack => "step back",
+            HistoryEvent::Measure => "measure",
+        }
+    }
+}
+
+impl History {
+    // to_dot returns the history as a Graphviz DOT graph, e.g. to render it with
+    // dot -Tsvg history.dot -o history.svg
+    //
+    // The configurations of an entry are on the same rank,
+    // and the configurations resulting from interferences are drawn in red
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph history {\n    rankdir=LR;\n    node [shape=box];\n");
+
+        for (e, entry) in self.entries.iter().enumerate() {
+            let event = entry.event.name();
+            let _ = write!(dot, "    {{ rank=same;");
+            for i in 0..entry.configurations.len() {
+                let _ = write!(dot, " \"{e}_{i}\";");
+            }
+            let _ = writeln!(dot, " }}");
+
+            for (i, node) in entry.configurations.iter().enumerate() {
+                let color = if node.parents.len() > 1 {
+                    "red"
+                } else {
+                    "black"
+                };
+                let _ = writeln!(
+                    dot,
+                    "    \"{e}_{i}\" [label=\"{event} {} #{i}\\np = {:.6}\\n{} cells\", color={color}];",
+                    entry.step_count,
+                    node.amplitude.norm_sqr(),
+                    node.living_cells,
+                );
+
+                for edge in node.parents.iter() {
+                    let mut label = format!("{:.4}{:+.4}i", edge.factor.re, edge.factor.im);
+                    for square in edge.split_squares.iter() {
+                        let _ = write!(label, "\\nsplit ({}, {})", square.x, square.y);
+                    }
+                    let _ = writeln!(
+                        dot,
+                        "    \"{}_{}\" -> \"{e}_{i}\" [label=\"{label}\"];",
+                        e - 1,
+                        edge.parent,
+                    );
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl<C: LivingCells> Universe<C> {
+    // with_history starts recording the history of the universe
+    // from its current state (see `history::History`)
+    pub fn with_history(mut self) -> Self {
+        self.history = Some(History::default());
+        self.record_history(HistoryEvent::Start, vec![Vec::new(); self.state.len()]);
+        self
+    }
+
+    // amplitudes_for_history returns the amplitudes of the configurations
+    // if the history is recorded, to compute the factors of the next edges
+    pub(crate) fn amplitudes_for_history(&self) -> Option<Vec<Complex<f64>>> {
+        self.history.as_ref()?;
+        Some(
+            self.state
+                .iter()
+                .map(|configuration| configuration.amplitude)
+                .collect(),
+        )
+    }
+
+    // record_history adds the current state to the history if it is recorded,
+    // parents[i] are the edges of the configuration i of the state
+    pub(crate) fn record_history(&mut self, event: HistoryEvent, parents: Vec<Vec<HistoryEdge>>) {
+        let Some(history) = self.history.as_mut() else {
+            return;
+        };
+
+        let configurations = self
+            .state
+            .iter()
+            .zip(parents)
+            .map(|(configuration, parents)| HistoryNode {
+                amplitude: configuration.amplitude,
+                living_cells: configuration.living_cells.len(),
+                parents,
+            })
+            .collect();
+        history.entries.push(HistoryEntry {
+            event,
+            step_count: self.step_count,
+            configurations,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::history::HistoryEvent;
+    use crate::universe::pruning::{PruningPolicy, PruningStrategy};
+    use crate::universe::types::{Coordinates, Universe};
+    use num::complex::Complex;
+
+    #[test]
+    fn test_history() {
+        let mut universe = Universe::new_from_files("fixtures/state_2_diagonal_cells.json", None)
+            .unwrap()
+            .with_history()
+            .with_seed(5);
+        for _ in 0..10 {
+            universe.step().unwrap();
+        }
+        universe.measure().unwrap();
+
+        let history = universe.history.clone().unwrap();
+        let events: Vec<HistoryEvent> = history.entries.iter().map(|entry| entry.event).collect();
+        assert_eq!(
+            events,
+            [
+                vec![HistoryEvent::Start],
+                vec![HistoryEvent::Step; 10],
+                vec![HistoryEvent::Measure]
+            ]
+            .concat()
+        );
+
+        // The single configuration splits in two on the first step
+        // and the two configurations merge on the fifth step
+        let first_step = &history.entries[1];
+        assert_eq!(first_step.configurations.len(), 2);
+        for node in first_step.configurations.iter() {
+            assert_eq!(node.parents.len(), 1);
+            assert_eq!(node.parents[0].parent, 0);
+            assert_eq!(
+                node.parents[0].split_squares,
+                [Coordinates { x: 10, y: 10 }]
+            );
+            assert!((node.parents[0].factor.norm_sqr() - 0.5).abs() < 1e-12);
+        }
+
+        // The amplitude of each configuration is the sum of the amplitudes
+        // of its parents multiplied by the factors of the edges
+        for (entry, next_entry) in history.entries.iter().zip(history.entries.iter().skip(1)) {
+            for node in next_entry.configurations.iter() {
+                let mut amplitude = Complex::new(0., 0.);
+                for edge in node.parents.iter() {
+                    assert!(edge.parent < entry.configurations.len());
+                    amplitude += entry.configurations[edge.parent].amplitude * edge.factor;
+                }
+                assert!((amplitude - node.amplitude).norm() < 1e-12);
+            }
+        }
+        assert_eq!(history.entries[5].configurations.len(), 1);
+        assert_eq!(history.entries[5].configurations[0].parents.len(), 2);
+        let last_entry = history.entries.last().unwrap();
+        assert_eq!(last_entry.configurations.len(), universe.state.len());
+
+        let dot = history.to_dot();
+        assert!(dot.starts_with("digraph history {"));
+        assert!(dot.contains("\"0_0\" -> \"1_1\""));
+    }
+
+    #[test]
+    fn test_history_factors() {
+        struct Test {
+            content: &'static str,
+            pruning: PruningPolicy,
+        }
+
+        let tests = [
+            // The renormalization of the pruning is included in the factors
+            Test {
+                content: r#"[{"amplitude": {"re": 1, "im": 0}, "living_cells": [[10, 10], [11, 11]]}]"#,
+                pruning: PruningPolicy {
+                    strategy: PruningStrategy::TopK { k: 1 },
+                    renormalize: true,
+                },
+            },
+            // The configuration without amplitude has no child edge
+            Test {
+                content: r#"[
+                    {"amplitude": {"re": 1, "im": 0}, "living_cells": [[10, 10], [11, 11]]},
+                    {"amplitude": {"re": 0, "im": 0}, "living_cells": [[20, 20], [21, 21]]}
+                ]"#,
+                pruning: PruningPolicy {
+                    strategy: PruningStrategy::Absolute { threshold: 0. },
+                    renormalize: false,
+                },
+            },
+        ];
+
+        for t in tests {
+            let mut universe = Universe::new_from_str(t.content)
+                .unwrap()
+                .with_pruning(t.pruning)
+                .unwrap()
+                .with_history();
+            for _ in 0..4 {
+                universe.step().unwrap();
+            }
+
+            let history = universe.history.unwrap();
+            for (entry, next_entry) in history.entries.iter().zip(history.entries.iter().skip(1)) {
+                for node in next_entry.configurations.iter() {
+                    let mut amplitude = Complex::new(0., 0.);
+                    for edge in node.parents.iter() {
+                        assert!(edge.factor.is_finite(), "{:?}", t.pruning);
+                        amplitude += entry.configurations[edge.parent].amplitude * edge.factor;
+                    }
+                    assert!(
+                        (amplitude - node.amplitude).norm() < 1e-12,
+                        "{:?}",
+                        t.pruning
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,37 +1,71 @@
 use num::Zero;
-use sha2::{Digest, Sha256};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 use std::collections::HashMap;
 
+use super::living_cells::LivingCells;
+use super::pruning::PruningReport;
 use super::types::*;
+use crate::Error;
 
-impl Universe {
-    pub fn solve_interference(&mut self) {
-        let mut configurations_hash: HashMap<String, usize> = HashMap::new();
-
-        for i in 0..self.state.len() {
-            let mut sorted_living_cells = self.state[i]
-                .living_cells
-                .keys()
-                .cloned()
-                .collect::<Vec<Coordinates>>();
-            sorted_living_cells.sort_unstable();
-
-            // Create a string with the sorted_living_cells coordinates
-            // Need to check optimization against map or manually iterating
-            let sorted_living_cells_string: String =
-                sorted_living_cells
-                    .iter()
-                    .fold(String::from(""), |acc, coordinates| {
-                        acc + &coordinates.x.to_string() + ";" + &coordinates.y.to_string() + ";"
-                    });
-
-            let mut hasher = Sha256::new();
-            hasher.update(sorted_living_cells_string);
-            let configuration_hash = hasher.finalize();
-            let hex_configuration_hash = base16ct::lower::encode_string(&configuration_hash);
-
-            match configurations_hash.entry(hex_configuration_hash) {
+// Configurations whose probability is below this value after the interferences
+// are destroyed by them, whatever the pruning policy (the rounding errors
+// rarely let the amplitudes of cancelled configurations be exactly 0)
+const CANCELLATION_TOLERANCE: f64 = 1e-24;
+
+impl<C: LivingCells> Universe<C> {
+    // solve_interference merges the configurations having the same living cells
+    // by summing their amplitudes
+    //
+    // The configurations are indexed by the interference hash of their living cells,
+    // two configurations with the same hash are compared to detect collisions
+    //
+    // The configurations cancelled by destructive interferences are removed,
+    // then the configurations are pruned according to the pruning policy of the universe
+    // and the discarded configurations are returned (see `Universe::prune`)
+    pub fn solve_interference(&mut self) -> Result<PruningReport, Error> {
+        Ok(self.solve_interference_with_indexes()?.0)
+    }
+
+    // solve_interference_with_indexes solves the interferences like `solve_interference`
+    // and also returns the index of each configuration in the new state,
+    // which is the index of the configuration it merged into if it was merged,
+    // or None if it was cancelled or pruned, and the renormalization factor
+    // of the pruning (see `Universe::prune_with_kept`)
+    pub(crate) fn solve_interference_with_indexes(
+        &mut self,
+    ) -> Result<(PruningReport, Vec<Option<usize>>, f64), Error> {
+        let mut configurations_hash: HashMap<u64, usize> = HashMap::new();
+        let mut merged_into: Vec<Option<usize>> = vec![None; self.state.len()];
+
+        // The hashes are computed in parallel with the parallel feature,
+        // the configurations are then merged in order in both cases
+        #[cfg(feature = "parallel")]
+        let hashes: Vec<u64> = self
+            .state
+            .par_iter()
+            .map(|configuration| configuration.living_cells.interference_hash())
+            .collect();
+        #[cfg(not(feature = "parallel"))]
+        let hashes: Vec<u64> = self
+            .state
+            .iter()
+            .map(|configuration| configuration.living_cells.interference_hash())
+            .collect();
+
+        for (i, hash) in hashes.into_iter().enumerate() {
+            let mut configuration_hash = hash;
+
+            // Colliding configurations are indexed by the next free hash
+            while let Occupied(entry) = configurations_hash.entry(configuration_hash) {
+                if self.state[*entry.get()].living_cells == self.state[i].living_cells {
+                    break;
+                }
+                configuration_hash = configuration_hash.wrapping_add(1);
+            }
+
+            match configurations_hash.entry(configuration_hash) {
                 Vacant(entry) => {
                     entry.insert(i);
                 }
@@ -47,21 +81,147 @@ impl Universe {
 
                     self.state[configuration_i].amplitude += current_amplitude;
                     self.state[i].amplitude.set_zero();
+                    merged_into[i] = Some(configuration_i);
 
-                    for coordinates in self.state[i].living_cells.keys() {
-                        *self.combined_state.get_mut(coordinates).unwrap() += norm_delta;
+                    for coordinates in self.state[i].living_cells.cells() {
+                        *self.combined_state.get_mut(&coordinates).ok_or_else(|| {
+                            Error::Internal(format!(
+                                "living cell {coordinates:?} is missing from the combined state"
+                            ))
+                        })? += norm_delta;
                     }
                 }
             }
         }
 
-        self.state.retain(|configuration| {
-            configuration.amplitude.re > 0.001
-                || configuration.amplitude.re < -0.001
-                || configuration.amplitude.im > 0.001
-                || configuration.amplitude.im < -0.001
-        });
-        self.combined_state
-            .retain(|_, norm_sum| *norm_sum > 0.00001); // Care maybe performance issues
+        // The configurations merged into another one or cancelled are removed,
+        // then the pruned ones
+        let removed: Vec<bool> = merged_into
+            .iter()
+            .zip(self.state.iter())
+            .map(|(merged, configuration)| {
+                merged.is_some() || configuration.amplitude.norm_sqr() < CANCELLATION_TOLERANCE
+            })
+            .collect();
+        let cancelled = removed
+            .iter()
+            .zip(merged_into.iter())
+            .any(|(removed, merged)| *removed && merged.is_none());
+        let mut merged_indexes: Vec<Option<usize>> = Vec::with_capacity(removed.len());
+        let mut merged_state_len = 0;
+        for removed in removed.iter() {
+            merged_indexes.push(if *removed {
+                None
+            } else {
+                merged_state_len += 1;
+                Some(merged_state_len - 1)
+            });
+        }
+        let mut removed_iter = removed.iter();
+        self.state
+            .retain(|_| !removed_iter.next().unwrap_or(&false));
+
+        // The cells only alive in cancelled configurations are not alive anymore,
+        // the combined state is computed again to remove them
+        if cancelled {
+            self.compute_combined_state();
+        }
+
+        let (report, kept, renormalization) = self.prune_with_kept();
+        let mut pruned_indexes: Vec<Option<usize>> = Vec::with_capacity(kept.len());
+        let mut pruned_state_len = 0;
+        for kept in kept {
+            pruned_indexes.push(if kept {
+                pruned_state_len += 1;
+                Some(pruned_state_len - 1)
+            } else {
+                None
+            });
+        }
+
+        let indexes = merged_into
+            .iter()
+            .enumerate()
+            .map(|(i, merged)| merged_indexes[merged.unwrap_or(i)])
+            .map(|index| index.and_then(|index| pruned_indexes[index]))
+            .collect();
+        Ok((report, indexes, renormalization))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::pruning::{PruningPolicy, PruningStrategy};
+    use crate::universe::types::{Coordinates, Universe};
+    use num::complex::Complex;
+
+    #[test]
+    fn test_solve_interference() {
+        let content = r#"[
+            {
+                "amplitude": {"re": 0.5, "im": 0},
+                "living_cells": [[{"x": 0, "y": 0}, false], [{"x": 3, "y": -2}, false]]
+            },
+            {
+                "amplitude": {"re": 0.5, "im": 0},
+                "living_cells": [[{"x": 3, "y": -2}, false], [{"x": 0, "y": 0}, false]]
+            },
+            {
+                "amplitude": {"re": 0, "im": 0.5},
+                "living_cells": [[{"x": 0, "y": 0}, false]]
+            },
+            {
+                "amplitude": {"re": 0, "im": -0.5},
+                "living_cells": [[{"x": 0, "y": 0}, false]]
+            }
+        ]"#;
+        let mut universe = Universe::new_from_str(content).unwrap();
+        universe.solve_interference().unwrap();
+
+        // The first two configurations interfere constructively
+        // and the last two destructively
+        assert_eq!(universe.state.len(), 1);
+        assert_eq!(universe.state[0].amplitude, Complex::new(1., 0.));
+        assert_eq!(universe.state[0].living_cells.len(), 2);
+        assert!((universe.combined_state[&Coordinates { x: 0, y: 0 }] - 1.).abs() < 1e-12);
+        assert!((universe.combined_state[&Coordinates { x: 3, y: -2 }] - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_solve_interference_cancellation() {
+        // The configurations with the living cell (0, 0) cancel each other
+        let content = r#"[
+            {"amplitude": {"re": 0.5, "im": 0}, "living_cells": [[0, 0]]},
+            {"amplitude": {"re": 0.7071067811865476, "im": 0}, "living_cells": [[1, 1]]},
+            {"amplitude": {"re": -0.5, "im": 0}, "living_cells": [[0, 0]]}
+        ]"#;
+
+        // The cancelled configuration is removed even if the pruning policy keeps everything
+        let strategies = [
+            PruningStrategy::TopK { k: 100 },
+            PruningStrategy::Absolute { threshold: 0. },
+            PruningStrategy::Relative { threshold: 0. },
+        ];
+
+        for strategy in strategies {
+            let policy = PruningPolicy {
+                strategy,
+                renormalize: false,
+            };
+            let mut universe = Universe::new_from_str(content)
+                .unwrap()
+                .with_pruning(policy)
+                .unwrap();
+            let (report, indexes, _) = universe.solve_interference_with_indexes().unwrap();
+
+            assert_eq!(universe.state.len(), 1, "{strategy:?}");
+            assert_eq!(universe.state[0].living_cells.len(), 1);
+            assert_eq!(report.configurations, 0);
+            assert_eq!(indexes, [None, Some(0), None]);
+            assert!(!universe
+                .combined_state
+                .contains_key(&Coordinates { x: 0, y: 0 }));
+            assert_eq!(universe.combined_state.len(), 1);
+        }
     }
 }
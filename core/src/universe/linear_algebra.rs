@@ -0,0 +1,122 @@
+use num::complex::Complex;
+
+// Maximum number of sweeps of the Jacobi eigenvalue algorithm,
+// it usually converges in less than 10 sweeps
+const MAX_JACOBI_SWEEPS: usize = 100;
+
+// hermitian_eigenvalues returns the eigenvalues of a Hermitian matrix in ascending order
+//
+// The n * n Hermitian matrix H = X + iY is embedded in the 2n * 2n real symmetric matrix
+//     [X -Y]
+//     [Y  X]
+// whose eigenvalues are the ones of H, each one twice, and which is diagonalized
+// with the cyclic Jacobi eigenvalue algorithm
+pub(crate) fn hermitian_eigenvalues(matrix: &[Vec<Complex<f64>>]) -> Vec<f64> {
+    let n = matrix.len();
+    let mut a = vec![vec![0.; 2 * n]; 2 * n];
+    for (i, row) in matrix.iter().enumerate() {
+        for (j, entry) in row.iter().enumerate() {
+            a[i][j] = entry.re;
+            a[i + n][j + n] = entry.re;
+            a[i][j + n] = -entry.im;
+            a[i + n][j] = entry.im;
+        }
+    }
+
+    let mut eigenvalues = symmetric_eigenvalues(a);
+    eigenvalues.sort_by(f64::total_cmp);
+    eigenvalues.into_iter().step_by(2).collect()
+}
+
+// symmetric_eigenvalues returns the eigenvalues of a real symmetric matrix
+// with the cyclic Jacobi eigenvalue algorithm: each sweep applies a rotation
+// zeroing each off-diagonal entry, until the off-diagonal entries are negligible
+fn symmetric_eigenvalues(mut a: Vec<Vec<f64>>) -> Vec<f64> {
+    let n = a.len();
+    let norm: f64 = a.iter().flatten().map(|entry| entry * entry).sum();
+
+    for _ in 0..MAX_JACOBI_SWEEPS {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|p| (p + 1..n).map(move |q| (p, q)))
+            .map(|(p, q)| a[p][q] * a[p][q])
+            .sum();
+        if off_diagonal <= norm * 1e-30 {
+            break;
+        }
+
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[p][q] == 0. {
+                    continue;
+                }
+
+                // The rotation of angle θ with cot(2θ) = (a_qq - a_pp) / (2 a_pq)
+                // zeroes a_pq, t = tan(θ) is the smallest root of t² + 2 t cot(2θ) - 1 = 0
+                let theta = (a[q][q] - a[p][p]) / (2. * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.).sqrt());
+                let c = 1. / (t * t + 1.).sqrt();
+                let s = t * c;
+
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (top, bottom) = a.split_at_mut(q);
+                for (apk, aqk) in top[p].iter_mut().zip(bottom[0].iter_mut()) {
+                    (*apk, *aqk) = (c * *apk - s * *aqk, s * *apk + c * *aqk);
+                }
+            }
+        }
+    }
+
+    (0..n).map(|i| a[i][i]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::linear_algebra;
+    use num::complex::Complex;
+
+    #[test]
+    fn test_hermitian_eigenvalues() {
+        let c = |re, im| Complex::new(re, im);
+
+        struct Test {
+            matrix: Vec<Vec<Complex<f64>>>,
+            exp: Vec<f64>,
+        }
+
+        let tests = [
+            Test {
+                matrix: vec![vec![c(2., 0.), c(0., 1.)], vec![c(0., -1.), c(2., 0.)]],
+                exp: vec![1., 3.],
+            },
+            Test {
+                matrix: vec![
+                    vec![c(0.5, 0.), c(0.5, 0.), c(0., 0.)],
+                    vec![c(0.5, 0.), c(0.5, 0.), c(0., 0.)],
+                    vec![c(0., 0.), c(0., 0.), c(0., 0.)],
+                ],
+                exp: vec![0., 0., 1.],
+            },
+            Test {
+                matrix: vec![
+                    vec![c(4., 0.), c(1., -2.), c(0., 0.)],
+                    vec![c(1., 2.), c(-1., 0.), c(0., 1.)],
+                    vec![c(0., 0.), c(0., -1.), c(3., 0.)],
+                ],
+                // Roots of the characteristic polynomial -λ³ + 6λ² + λ - 31
+                exp: vec![-2.028296262910133, 3.1033122990087403, 4.924983963901392],
+            },
+        ];
+
+        for t in tests {
+            let got = linear_algebra::hermitian_eigenvalues(&t.matrix);
+            assert_eq!(got.len(), t.exp.len());
+            for (got, exp) in got.iter().zip(t.exp.iter()) {
+                assert!((got - exp).abs() < 1e-9, "got {got}, expected {exp}");
+            }
+        }
+    }
+}
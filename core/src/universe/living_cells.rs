@@ -0,0 +1,77 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use super::types::Coordinates;
+
+// LivingCells is the storage of the living cells of a configuration
+//
+// Two storages are available:
+//     - CellSet (the default), a sorted vector of coordinates, suited to sparse patterns
+//     - TiledCells, 8 * 8 tiles of cells stored as bitmasks, suited to dense patterns
+//
+// The storage of a universe is chosen with the type parameter of the universe,
+// e.g. Universe<TiledCells>, see `Universe::into_storage` to convert a universe
+//
+// Two values of a storage containing the same living cells must be equal
+// and have the same interference hash, whatever the order the cells were inserted in
+pub trait LivingCells: Clone + Debug + Default + PartialEq + Eq + Hash + Ord + Send + Sync {
+    // from_unsorted creates the storage from cells in any order
+    fn from_unsorted(cells: Vec<Coordinates>) -> Self;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains(&self, coordinates: &Coordinates) -> bool;
+
+    // insert returns false if the cell was already alive
+    fn insert(&mut self, coordinates: Coordinates) -> bool;
+
+    // remove returns false if the cell was not alive
+    fn remove(&mut self, coordinates: &Coordinates) -> bool;
+
+    fn cells(&self) -> Box<dyn Iterator<Item = Coordinates> + '_>;
+
+    // square_state returns whether each cell of the 2 * 2 square
+    // whose top left cell is (x_min, y_min) is alive, in the order
+    // [0] [1]
+    // [2] [3]
+    // with [0] = (x_min, y_min), [1] = (x_min, y_min + 1) and [2] = (x_min + 1, y_min)
+    fn square_state(&self, x_min: i32, y_min: i32) -> [bool; 4] {
+        [
+            self.contains(&Coordinates { x: x_min, y: y_min }),
+            self.contains(&Coordinates {
+                x: x_min,
+                y: y_min + 1,
+            }),
+            self.contains(&Coordinates {
+                x: x_min + 1,
+                y: y_min,
+            }),
+            self.contains(&Coordinates {
+                x: x_min + 1,
+                y: y_min + 1,
+            }),
+        ]
+    }
+
+    // interference_hash is the hash used to find the configurations
+    // that interfere in `Universe::solve_interference`
+    fn interference_hash(&self) -> u64;
+}
+
+// mix_hash mixes the bits of a 64 bits value (splitmix64 finalizer)
+// so that sums of mixed values rarely collide
+pub(crate) fn mix_hash(value: u64) -> u64 {
+    let mut hash = value.wrapping_add(0x9e3779b97f4a7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+// pack_pair packs two i32 in a u64
+pub(crate) fn pack_pair(a: i32, b: i32) -> u64 {
+    ((a as u32 as u64) << 32) | b as u32 as u64
+}
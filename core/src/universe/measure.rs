@@ -1,41 +1,254 @@
 use num::complex::Complex;
 use rand::distributions::{Distribution, WeightedIndex};
-use rand::thread_rng;
+use std::collections::BTreeMap;
 
+use super::history::{HistoryEdge, HistoryEvent};
+use super::living_cells::LivingCells;
 use super::types::*;
+use crate::Error;
 
-impl Universe {
+impl<C: LivingCells> Universe<C> {
     // This function measure the state of the universe
     // destroying all configurations except 1
     // This configuration is chosen randomly
     // according to a probability distribution
     // computed from the amplitudes of the configurations
-    pub fn measure(&mut self) {
+    // and the random number generator of the universe
+    //
+    // An AmplitudesError is returned if the amplitudes can't be used as probabilities
+    pub fn measure(&mut self) -> Result<(), Error> {
         if self.state.len() <= 1 {
-            return;
+            return Ok(());
         }
+        validate_amplitudes(&self.state)?;
+
+        // The order of the configurations in the state can change between runs,
+        // so the configurations are sorted by living cells
+        // for the chosen configuration to only depend on the random number generator
+        let mut sorted_indexes: Vec<usize> = (0..self.state.len()).collect();
+        sorted_indexes.sort_by_key(|i| &self.state[*i].living_cells);
 
         let mut state_weights: Vec<f64> = Vec::new();
 
-        for configuration in self.state.iter() {
-            let weight = configuration.amplitude.norm_sqr();
+        for i in sorted_indexes.iter() {
+            let weight = self.state[*i].amplitude.norm_sqr();
             state_weights.push(weight);
         }
 
-        let state_distribution = WeightedIndex::new(&state_weights).unwrap();
-        let mut rng = thread_rng();
-        let chosen_configuration_index = state_distribution.sample(&mut rng);
+        let state_distribution =
+            WeightedIndex::new(&state_weights).map_err(|err| Error::Internal(err.to_string()))?;
+        let chosen_configuration_index = sorted_indexes[state_distribution.sample(&mut self.rng)];
         let mut chosen_configuration = self.state.swap_remove(chosen_configuration_index);
+        let edge = HistoryEdge {
+            parent: chosen_configuration_index,
+            factor: chosen_configuration.amplitude.inv(),
+            split_squares: Vec::new(),
+        };
         chosen_configuration.amplitude = Complex::new(1.0, 0.0);
         self.state = vec![chosen_configuration];
         self.compute_combined_state();
+        self.record_history(HistoryEvent::Measure, vec![vec![edge]]);
+        Ok(())
+    }
+
+    // This function measures only the given cells of the universe
+    //
+    // The outcome of the measure (whether each cell is alive or dead)
+    // is chosen randomly with the probability of each outcome, which is
+    // the sum of the probabilities of the configurations giving this outcome
+    //
+    // All configurations that are not consistent with the outcome are destroyed
+    // and the amplitudes of the remaining ones are renormalized,
+    // so that they stay superposed unlike with a global measure
+    //
+    // The returned outcome contains true for each living cell
+    // and false for each dead cell, in the order of the given cells
+    pub fn measure_region(&mut self, cells: &[Coordinates]) -> Result<Vec<bool>, Error> {
+        validate_amplitudes(&self.state)?;
+        let configuration_outcomes: Vec<Vec<bool>> = self
+            .state
+            .iter()
+            .map(|configuration| {
+                cells
+                    .iter()
+                    .map(|coordinates| configuration.living_cells.contains(coordinates))
+                    .collect()
+            })
+            .collect();
+
+        // Outcomes are stored in a BTreeMap so that their order,
+        // and then the chosen outcome, only depends on the random number generator
+        let mut outcome_probabilities: BTreeMap<&Vec<bool>, f64> = BTreeMap::new();
+        for (configuration, outcome) in self.state.iter().zip(configuration_outcomes.iter()) {
+            *outcome_probabilities.entry(outcome).or_insert(0.0) +=
+                configuration.amplitude.norm_sqr();
+        }
+
+        let outcomes: Vec<&Vec<bool>> = outcome_probabilities.keys().cloned().collect();
+        let outcome_weights: Vec<f64> = outcome_probabilities.values().cloned().collect();
+        let outcome_distribution =
+            WeightedIndex::new(&outcome_weights).map_err(|err| Error::Internal(err.to_string()))?;
+        let chosen_outcome_index = outcome_distribution.sample(&mut self.rng);
+        let chosen_outcome = outcomes[chosen_outcome_index].clone();
+
+        // The remaining configurations keep the total probability of the state
+        let total_probability: f64 = outcome_weights.iter().sum();
+        let renormalization = (total_probability / outcome_weights[chosen_outcome_index]).sqrt();
+
+        let parents: Vec<Vec<HistoryEdge>> = configuration_outcomes
+            .iter()
+            .enumerate()
+            .filter(|(_, outcome)| **outcome == chosen_outcome)
+            .map(|(parent, _)| {
+                vec![HistoryEdge {
+                    parent,
+                    factor: Complex::new(renormalization, 0.),
+                    split_squares: Vec::new(),
+                }]
+            })
+            .collect();
+
+        let mut configuration_outcomes = configuration_outcomes.iter();
+        self.state
+            .retain(|_| configuration_outcomes.next() == Some(&chosen_outcome));
+        for configuration in self.state.iter_mut() {
+            configuration.amplitude *= renormalization;
+        }
+        self.compute_combined_state();
+        self.record_history(HistoryEvent::Measure, parents);
+
+        Ok(chosen_outcome)
     }
 }
 
-// Need to implement tests
 #[cfg(test)]
 mod tests {
+    use crate::universe::types::{Coordinates, Universe};
+    use rand_chacha::ChaCha8Rng;
+
+    // Run steps on the universe and measure it each time it has more than 4 configurations,
+    // returning the living cells of the configuration kept by each measure
+    fn measured_cells(universe: &mut Universe, steps: usize) -> Vec<Vec<(i32, i32)>> {
+        let mut measured = Vec::new();
+        for _ in 0..steps {
+            universe.step().unwrap();
+            if universe.state.len() > 4 {
+                universe.measure().unwrap();
+                let cells: Vec<(i32, i32)> = universe.state[0]
+                    .living_cells
+                    .iter()
+                    .map(|coordinates| (coordinates.x, coordinates.y))
+                    .collect();
+                measured.push(cells);
+            }
+        }
+        measured
+    }
 
     #[test]
-    fn test_measure() {}
+    fn test_measure() {
+        let universe = Universe::new_from_files("fixtures/state_grid_20.json", None)
+            .unwrap()
+            .with_seed(42);
+
+        let exp = measured_cells(&mut universe.clone(), 30);
+        assert!(!exp.is_empty());
+        assert_eq!(measured_cells(&mut universe.clone(), 30), exp);
+
+        let mut universe = universe;
+        universe.measure().unwrap();
+        assert_eq!(universe.state.len(), 1);
+        assert_eq!(universe.state[0].amplitude.norm_sqr(), 1.);
+    }
+
+    #[test]
+    fn test_measure_region() {
+        let content = r#"[
+            {
+                "amplitude": {"re": 0.5, "im": 0},
+                "living_cells": [[{"x": 0, "y": 0}, false], [{"x": 5, "y": 5}, false]]
+            },
+            {
+                "amplitude": {"re": 0, "im": 0.5},
+                "living_cells": [[{"x": 0, "y": 0}, false], [{"x": 6, "y": 6}, false]]
+            },
+            {
+                "amplitude": {"re": -0.5, "im": 0},
+                "living_cells": [[{"x": 1, "y": 1}, false], [{"x": 5, "y": 5}, false]]
+            },
+            {
+                "amplitude": {"re": 0.5, "im": 0},
+                "living_cells": [[{"x": 1, "y": 1}, false], [{"x": 6, "y": 6}, false]]
+            }
+        ]"#;
+
+        for seed in 0..10 {
+            let mut universe = Universe::new_from_str(content).unwrap().with_seed(seed);
+
+            let outcome = universe
+                .measure_region(&[Coordinates { x: 0, y: 0 }])
+                .unwrap();
+            assert_eq!(universe.state.len(), 2);
+            let probability: f64 = universe
+                .state
+                .iter()
+                .map(|configuration| configuration.amplitude.norm_sqr())
+                .sum();
+            assert!((probability - 1.).abs() < 1e-12);
+            for configuration in universe.state.iter() {
+                assert_eq!(
+                    configuration
+                        .living_cells
+                        .contains(&Coordinates { x: 0, y: 0 }),
+                    outcome[0]
+                );
+                assert!((configuration.amplitude.norm_sqr() - 0.5).abs() < 1e-12);
+            }
+            assert_eq!(
+                universe
+                    .combined_state
+                    .contains_key(&Coordinates { x: 0, y: 0 }),
+                outcome[0]
+            );
+
+            // A cell alive in every remaining configuration is measured alive
+            // without destroying any configuration
+            let cell = universe.state[0]
+                .living_cells
+                .iter()
+                .next()
+                .unwrap()
+                .clone();
+            if universe.state[1].living_cells.contains(&cell) {
+                assert_eq!(universe.measure_region(&[cell]).unwrap(), vec![true]);
+                assert_eq!(universe.state.len(), 2);
+            }
+
+            let outcome = universe
+                .measure_region(&[Coordinates { x: 5, y: 5 }, Coordinates { x: 6, y: 6 }])
+                .unwrap();
+            assert_eq!(universe.state.len(), 1);
+            assert!(outcome == vec![true, false] || outcome == vec![false, true]);
+            assert!((universe.state[0].amplitude.norm_sqr() - 1.).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_measure_serialized_rng() {
+        let mut universe = Universe::new_from_files("fixtures/state_grid_20.json", None)
+            .unwrap()
+            .with_seed(7);
+        measured_cells(&mut universe, 10);
+
+        // A universe whose random number generator is restored
+        // from its serialization measures the same configurations
+        let serialized_rng = serde_json::to_string(&universe.rng).unwrap();
+        let mut restored = universe.clone();
+        restored.rng = serde_json::from_str::<ChaCha8Rng>(&serialized_rng).unwrap();
+
+        assert_eq!(
+            measured_cells(&mut restored, 20),
+            measured_cells(&mut universe, 20)
+        );
+    }
 }
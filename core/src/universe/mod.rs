@@ -1,6 +1,22 @@
+pub mod cell_set;
 pub mod combined_state;
+pub mod comparison;
+pub mod density_matrix;
+pub mod entanglement;
 pub mod files;
+pub mod history;
 pub mod interference;
+pub mod linear_algebra;
+pub mod living_cells;
 pub mod measure;
+pub mod normalization;
+pub mod observables;
+pub mod pruning;
+pub mod rules;
+pub mod rules_builder;
+pub mod snapshot;
 pub mod step;
+pub mod tiled_cells;
+pub mod topology;
+pub mod trajectories;
 pub mod types;
@@ -0,0 +1,71 @@
+use num::complex::Complex;
+
+use super::living_cells::LivingCells;
+use super::types::*;
+use crate::{AmplitudesError, Error};
+
+// Difference between the total probability of a state and 1
+// under which the state is considered normalized
+pub const NORMALIZATION_TOLERANCE: f64 = 1e-6;
+
+impl<C: LivingCells> Universe<C> {
+    // normalize scales the amplitudes of the configurations so that
+    // the total probability is 1 and computes the combined state again
+    //
+    // An AmplitudesError is returned if the amplitudes can't be used as probabilities
+    pub fn normalize(&mut self) -> Result<(), Error> {
+        validate_amplitudes(&self.state)?;
+
+        let scale = Complex::new(1. / self.total_probability().sqrt(), 0.);
+        for configuration in self.state.iter_mut() {
+            configuration.amplitude *= scale;
+        }
+        self.compute_combined_state();
+        Ok(())
+    }
+
+    // validate_normalization returns an AmplitudesError::NotNormalized
+    // if the total probability differs from 1 by more than NORMALIZATION_TOLERANCE
+    //
+    // An unnormalized state is still valid, e.g. after some probability is pruned,
+    // but its combined state isn't a probability, so the error is meant to be shown as a warning
+    pub fn validate_normalization(&self) -> Result<(), AmplitudesError> {
+        let total_probability = self.total_probability();
+        if (total_probability - 1.).abs() > NORMALIZATION_TOLERANCE {
+            return Err(AmplitudesError::NotNormalized { total_probability });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::types::{Coordinates, LoadOptions, Universe};
+    use crate::AmplitudesError;
+
+    #[test]
+    fn test_normalize() {
+        let content = r#"[
+            {"amplitude": {"re": 1, "im": 0}, "living_cells": [[0, 0]]},
+            {"amplitude": {"re": 0, "im": 1}, "living_cells": [[0, 0], [1, 1]]}
+        ]"#;
+
+        let mut universe = Universe::new_from_str(content).unwrap();
+        assert_eq!(
+            universe.validate_normalization(),
+            Err(AmplitudesError::NotNormalized {
+                total_probability: 2.
+            })
+        );
+        assert_eq!(universe.combined_state[&Coordinates { x: 0, y: 0 }], 2.);
+
+        universe.normalize().unwrap();
+        assert!(universe.validate_normalization().is_ok());
+        assert!((universe.combined_state[&Coordinates { x: 0, y: 0 }] - 1.).abs() < 1e-12);
+        assert!((universe.combined_state[&Coordinates { x: 1, y: 1 }] - 0.5).abs() < 1e-12);
+
+        let options = LoadOptions { normalize: true };
+        let loaded = Universe::new_from_str_with(content, options).unwrap();
+        assert_eq!(loaded.state, universe.state);
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::living_cells::LivingCells;
+use super::types::*;
+
+// The observables are expectation values computed from a state,
+// n(x) being 1 if the cell x is alive and 0 otherwise
+//
+// The probabilities of the configurations are divided by the total probability
+// of the state, so the observables of an unnormalized state are the ones
+// of the normalized state (the occupations are then the combined state of
+// a normalized universe), and they are all 0 for a state with no probability
+
+// total_probability returns the sum of the probabilities of the configurations,
+// or 1 if it is 0 to avoid dividing by 0
+fn total_probability<C>(state: &State<C>) -> f64 {
+    let total_probability: f64 = state
+        .iter()
+        .map(|configuration| configuration.amplitude.norm_sqr())
+        .sum();
+    if total_probability > 0. {
+        total_probability
+    } else {
+        1.
+    }
+}
+
+// occupation returns ⟨n(x)⟩, the probability that the cell is alive
+pub fn occupation<C: LivingCells>(state: &State<C>, cell: &Coordinates) -> f64 {
+    let probability: f64 = state
+        .iter()
+        .filter(|configuration| configuration.living_cells.contains(cell))
+        .map(|configuration| configuration.amplitude.norm_sqr())
+        .sum();
+    probability / total_probability(state)
+}
+
+// occupations returns ⟨n(x)⟩ for each cell alive in at least one configuration
+pub fn occupations<C: LivingCells>(state: &State<C>) -> HashMap<Coordinates, f64> {
+    let total_probability = total_probability(state);
+    let mut occupations: HashMap<Coordinates, f64> = HashMap::new();
+    for configuration in state.iter() {
+        let probability = configuration.amplitude.norm_sqr() / total_probability;
+        for cell in configuration.living_cells.cells() {
+            *occupations.entry(cell).or_insert(0.) += probability;
+        }
+    }
+    occupations
+}
+
+// correlation returns the connected two-point correlation ⟨n(a)n(b)⟩ − ⟨n(a)⟩⟨n(b)⟩,
+// which is 0 if the cells are alive independently of each other
+pub fn correlation<C: LivingCells>(state: &State<C>, a: &Coordinates, b: &Coordinates) -> f64 {
+    let total_probability = total_probability(state);
+    let (mut both, mut alive_a, mut alive_b) = (0., 0., 0.);
+    for configuration in state.iter() {
+        let probability = configuration.amplitude.norm_sqr() / total_probability;
+        let contains_a = configuration.living_cells.contains(a);
+        let contains_b = configuration.living_cells.contains(b);
+        if contains_a {
+            alive_a += probability;
+        }
+        if contains_b {
+            alive_b += probability;
+        }
+        if contains_a && contains_b {
+            both += probability;
+        }
+    }
+    both - alive_a * alive_b
+}
+
+// population_distribution returns the probability of each number of living cells
+pub fn population_distribution<C: LivingCells>(state: &State<C>) -> BTreeMap<usize, f64> {
+    let total_probability = total_probability(state);
+    let mut distribution: BTreeMap<usize, f64> = BTreeMap::new();
+    for configuration in state.iter() {
+        *distribution
+            .entry(configuration.living_cells.len())
+            .or_insert(0.) += configuration.amplitude.norm_sqr() / total_probability;
+    }
+    distribution
+}
+
+// Center of mass and spread of the living cells
+//
+// The center of mass is the mean of the coordinates of the cells weighted by their occupation,
+// and the spread is the root mean square distance of the cells to the center of mass
+// (the coordinates are the ones of the state, even on a torus)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PopulationMoments {
+    pub center_of_mass: (f64, f64),
+    pub spread: f64,
+}
+
+// population_moments returns the center of mass and the spread of the living cells,
+// or None if no cell is alive
+pub fn population_moments<C: LivingCells>(state: &State<C>) -> Option<PopulationMoments> {
+    let total_probability = total_probability(state);
+
+    // The sums are computed on the configurations rather than on the occupations
+    // for their result not to depend on the order of a HashMap
+    let (mut population, mut x, mut y) = (0., 0., 0.);
+    for configuration in state.iter() {
+        let probability = configuration.amplitude.norm_sqr() / total_probability;
+        for cell in configuration.living_cells.cells() {
+            population += probability;
+            x += cell.x as f64 * probability;
+            y += cell.y as f64 * probability;
+        }
+    }
+    if population <= 0. {
+        return None;
+    }
+    let center_of_mass = (x / population, y / population);
+
+    let mut variance = 0.;
+    for configuration in state.iter() {
+        let probability = configuration.amplitude.norm_sqr() / total_probability;
+        for cell in configuration.living_cells.cells() {
+            let dx = cell.x as f64 - center_of_mass.0;
+            let dy = cell.y as f64 - center_of_mass.1;
+            variance += (dx * dx + dy * dy) * probability;
+        }
+    }
+
+    Some(PopulationMoments {
+        center_of_mass,
+        spread: (variance / population).sqrt(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::observables;
+    use crate::universe::types::{Coordinates, Universe};
+
+    // Two configurations with the same probability,
+    // the cells (0, 0) and (2, 0) are alive together and (0, 2) is alive alone
+    const CONTENT: &str = r#"[
+        {"amplitude": {"re": 1, "im": 0}, "living_cells": [[0, 0], [2, 0]]},
+        {"amplitude": {"re": 0, "im": 1}, "living_cells": [[0, 2]]}
+    ]"#;
+
+    #[test]
+    fn test_observables() {
+        let state = Universe::new_from_str(CONTENT).unwrap().state;
+        let c = |x, y| Coordinates { x, y };
+
+        assert_eq!(observables::occupation(&state, &c(0, 0)), 0.5);
+        assert_eq!(observables::occupation(&state, &c(1, 0)), 0.);
+        assert_eq!(observables::occupations(&state)[&c(0, 2)], 0.5);
+
+        struct Test {
+            a: Coordinates,
+            b: Coordinates,
+            exp: f64,
+        }
+
+        let tests = [
+            Test {
+                a: c(0, 0),
+                b: c(2, 0),
+                exp: 0.25,
+            },
+            Test {
+                a: c(0, 0),
+                b: c(0, 2),
+                exp: -0.25,
+            },
+            Test {
+                a: c(0, 0),
+                b: c(5, 5),
+                exp: 0.,
+            },
+        ];
+
+        for t in tests {
+            assert_eq!(observables::correlation(&state, &t.a, &t.b), t.exp);
+        }
+
+        let distribution = observables::population_distribution(&state);
+        assert_eq!(
+            distribution.into_iter().collect::<Vec<_>>(),
+            [(1, 0.5), (2, 0.5)]
+        );
+
+        let moments = observables::population_moments(&state).unwrap();
+        assert!((moments.center_of_mass.0 - 2. / 3.).abs() < 1e-12);
+        assert!((moments.center_of_mass.1 - 2. / 3.).abs() < 1e-12);
+        // Mean of the squared distances 8 / 9 + 20 / 9 + 20 / 9 over the 3 cells
+        assert!((moments.spread - (16_f64 / 9.).sqrt()).abs() < 1e-12);
+
+        assert_eq!(
+            observables::population_moments(&Universe::new().state),
+            None
+        );
+    }
+}
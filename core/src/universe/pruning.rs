@@ -0,0 +1,297 @@
+use num::complex::Complex;
+use serde::{Deserialize, Serialize};
+
+use super::living_cells::LivingCells;
+use super::types::Universe;
+use crate::Error;
+
+// The pruning policy defines which configurations are discarded
+// after each step, once the interferences are solved (see `Universe::prune`)
+//
+// Discarding the configurations with a negligible probability keeps the number
+// of configurations under control, at the cost of losing their probability,
+// unless renormalize is true: the amplitudes of the kept configurations are then scaled
+// so that the total probability is the same as before pruning
+//
+// Example (JSON):
+// {"strategy": {"type": "top_k", "k": 1000}, "renormalize": true}
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PruningPolicy {
+    pub strategy: PruningStrategy,
+    #[serde(default)]
+    pub renormalize: bool,
+}
+
+// - Absolute: discard the configurations whose probability is below the threshold
+// - Relative: discard the configurations whose probability is below the threshold
+//   times the probability of the most probable configuration
+// - TopK: keep only the k most probable configurations
+// - CumulativeProbability: keep the most probable configurations until their cumulative
+//   probability reaches the given fraction of the total probability
+//
+// With TopK and CumulativeProbability, configurations with the same probability
+// are kept in the order of the state
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PruningStrategy {
+    Absolute { threshold: f64 },
+    Relative { threshold: f64 },
+    TopK { k: usize },
+    CumulativeProbability { probability: f64 },
+}
+
+// The default policy discards the configurations whose amplitude
+// has a norm below 0.001, without renormalization
+impl Default for PruningPolicy {
+    fn default() -> Self {
+        Self {
+            strategy: PruningStrategy::Absolute { threshold: 1e-6 },
+            renormalize: false,
+        }
+    }
+}
+
+// Configurations and probability discarded by a pruning
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct PruningReport {
+    pub configurations: usize,
+    pub probability: f64,
+}
+
+impl PruningPolicy {
+    pub fn validate(&self) -> Result<(), Error> {
+        let valid = match self.strategy {
+            PruningStrategy::Absolute { threshold } | PruningStrategy::Relative { threshold } => {
+                (0. ..=1.).contains(&threshold)
+            }
+            PruningStrategy::TopK { k } => k > 0,
+            PruningStrategy::CumulativeProbability { probability } => {
+                probability > 0. && probability <= 1.
+            }
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidPruningPolicy(format!(
+                "{:?} is out of range",
+                self.strategy
+            )))
+        }
+    }
+
+    // kept returns whether each configuration is kept, given their probabilities
+    fn kept(&self, probabilities: &[f64]) -> Vec<bool> {
+        match self.strategy {
+            PruningStrategy::Absolute { threshold } => probabilities
+                .iter()
+                .map(|probability| *probability >= threshold)
+                .collect(),
+            PruningStrategy::Relative { threshold } => {
+                let max_probability = probabilities.iter().cloned().fold(0., f64::max);
+                probabilities
+                    .iter()
+                    .map(|probability| *probability >= threshold * max_probability)
+                    .collect()
+            }
+            PruningStrategy::TopK { k } => {
+                let mut kept = vec![false; probabilities.len()];
+                for i in sorted_by_probability(probabilities).into_iter().take(k) {
+                    kept[i] = true;
+                }
+                kept
+            }
+            PruningStrategy::CumulativeProbability { probability } => {
+                let target = probability * probabilities.iter().sum::<f64>();
+                let mut kept = vec![false; probabilities.len()];
+                let mut cumulative_probability = 0.;
+                for i in sorted_by_probability(probabilities) {
+                    if cumulative_probability >= target {
+                        break;
+                    }
+                    kept[i] = true;
+                    cumulative_probability += probabilities[i];
+                }
+                kept
+            }
+        }
+    }
+}
+
+// sorted_by_probability returns the indexes of the probabilities
+// from the highest probability to the lowest
+fn sorted_by_probability(probabilities: &[f64]) -> Vec<usize> {
+    let mut indexes: Vec<usize> = (0..probabilities.len()).collect();
+    indexes.sort_by(|a, b| probabilities[*b].total_cmp(&probabilities[*a]));
+    indexes
+}
+
+impl<C: LivingCells> Universe<C> {
+    // with_pruning sets the pruning policy applied after each step
+    pub fn with_pruning(mut self, pruning: PruningPolicy) -> Result<Self, Error> {
+        pruning.validate()?;
+        self.pruning = pruning;
+        Ok(self)
+    }
+
+    // prune discards the configurations according to the pruning policy of the universe
+    // and returns the number of discarded configurations and their total probability
+    //
+    // The combined state is computed again if some configurations are discarded
+    pub fn prune(&mut self) -> PruningReport {
+        self.prune_with_kept().0
+    }
+
+    // prune_with_kept prunes the configurations like `prune`
+    // and also returns whether each configuration was kept, and the factor
+    // by which the amplitudes of the kept configurations were multiplied
+    // by the renormalization (1 without renormalization)
+    pub(crate) fn prune_with_kept(&mut self) -> (PruningReport, Vec<bool>, f64) {
+        let probabilities: Vec<f64> = self
+            .state
+            .iter()
+            .map(|configuration| configuration.amplitude.norm_sqr())
+            .collect();
+        let kept = self.pruning.kept(&probabilities);
+
+        let mut report = PruningReport::default();
+        for (probability, _) in probabilities.iter().zip(kept.iter()).filter(|(_, k)| !**k) {
+            report.configurations += 1;
+            report.probability += probability;
+        }
+        if report.configurations == 0 {
+            return (report, kept, 1.);
+        }
+
+        let mut kept_iter = kept.iter();
+        self.state.retain(|_| *kept_iter.next().unwrap_or(&true));
+
+        let total_probability: f64 = probabilities.iter().sum();
+        let kept_probability = total_probability - report.probability;
+        let mut renormalization = 1.;
+        if self.pruning.renormalize && kept_probability > 0. {
+            renormalization = (total_probability / kept_probability).sqrt();
+            for configuration in self.state.iter_mut() {
+                configuration.amplitude *= Complex::new(renormalization, 0.);
+            }
+        }
+
+        self.compute_combined_state();
+        (report, kept, renormalization)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::pruning::{PruningPolicy, PruningStrategy};
+    use crate::universe::types::Universe;
+    use crate::Error;
+
+    // Four configurations with the probabilities 0.5, 0.3, 0.15 and 0.05
+    const CONTENT: &str = r#"[
+        {"amplitude": {"re": 0.3872983346207417, "im": 0}, "living_cells": [[0, 2]]},
+        {"amplitude": {"re": 0.7071067811865476, "im": 0}, "living_cells": [[0, 0]]},
+        {"amplitude": {"re": 0, "im": 0.22360679774997896}, "living_cells": [[0, 3]]},
+        {"amplitude": {"re": 0, "im": -0.5477225575051661}, "living_cells": [[0, 1]]}
+    ]"#;
+
+    #[test]
+    fn test_prune() {
+        struct Test {
+            strategy: PruningStrategy,
+            renormalize: bool,
+            exp_kept_cells: Vec<i32>,
+            exp_pruned_probability: f64,
+            exp_total_probability: f64,
+        }
+
+        let tests = [
+            Test {
+                strategy: PruningStrategy::Absolute { threshold: 0.1 },
+                renormalize: false,
+                exp_kept_cells: vec![2, 0, 1],
+                exp_pruned_probability: 0.05,
+                exp_total_probability: 0.95,
+            },
+            Test {
+                strategy: PruningStrategy::Relative { threshold: 0.5 },
+                renormalize: false,
+                exp_kept_cells: vec![0, 1],
+                exp_pruned_probability: 0.2,
+                exp_total_probability: 0.8,
+            },
+            Test {
+                strategy: PruningStrategy::TopK { k: 1 },
+                renormalize: true,
+                exp_kept_cells: vec![0],
+                exp_pruned_probability: 0.5,
+                exp_total_probability: 1.,
+            },
+            Test {
+                strategy: PruningStrategy::CumulativeProbability { probability: 0.9 },
+                renormalize: true,
+                exp_kept_cells: vec![2, 0, 1],
+                exp_pruned_probability: 0.05,
+                exp_total_probability: 1.,
+            },
+            Test {
+                strategy: PruningStrategy::TopK { k: 10 },
+                renormalize: true,
+                exp_kept_cells: vec![2, 0, 3, 1],
+                exp_pruned_probability: 0.,
+                exp_total_probability: 1.,
+            },
+        ];
+
+        for t in tests {
+            let policy = PruningPolicy {
+                strategy: t.strategy,
+                renormalize: t.renormalize,
+            };
+            let mut universe = Universe::new_from_str(CONTENT)
+                .unwrap()
+                .with_pruning(policy)
+                .unwrap();
+            let report = universe.prune();
+
+            let kept_cells: Vec<i32> = universe
+                .state
+                .iter()
+                .map(|configuration| configuration.living_cells.as_slice()[0].y)
+                .collect();
+            assert_eq!(kept_cells, t.exp_kept_cells, "{:?}", t.strategy);
+            assert_eq!(report.configurations, 4 - t.exp_kept_cells.len());
+            assert!((report.probability - t.exp_pruned_probability).abs() < 1e-12);
+
+            let total_probability: f64 = universe
+                .state
+                .iter()
+                .map(|configuration| configuration.amplitude.norm_sqr())
+                .sum();
+            assert!((total_probability - t.exp_total_probability).abs() < 1e-12);
+            let combined_probability: f64 = universe.combined_state.values().sum();
+            assert!((combined_probability - t.exp_total_probability).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_invalid_pruning_policy() {
+        let strategies = [
+            PruningStrategy::Absolute { threshold: -0.1 },
+            PruningStrategy::Relative { threshold: 1.5 },
+            PruningStrategy::TopK { k: 0 },
+            PruningStrategy::CumulativeProbability { probability: 0. },
+        ];
+
+        for strategy in strategies {
+            let policy = PruningPolicy {
+                strategy,
+                renormalize: false,
+            };
+            assert!(matches!(
+                Universe::new().with_pruning(policy),
+                Err(Error::InvalidPruningPolicy(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use num::complex::Complex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::step::index_to_square_state;
+use super::types::Rules;
+use crate::Error;
+
+// Maximum deviation from the identity allowed for the product
+// of the rules with their conjugate transpose
+pub const UNITARITY_TOLERANCE: f64 = 1e-6;
+
+// The default rules of the universe are stored in a rules file
+// shipped with the crate, see `get_default_rules`
+const DEFAULT_RULES: &str = include_str!("../../fixtures/rules_default.json");
+
+// A rules file contains the 16x16 operator matrix of the universe
+//
+// Each entry of the matrix is a complex number written either
+// in its cartesian form {"re": 0.5, "im": 0.5}
+// or in its polar form {"r": 1, "theta": 0.785} (theta is in radians)
+//
+// Example (JSON):
+// {
+//   "rules": [
+//     [{"re": 1, "im": 0}, {"re": 0, "im": 0}, ...],
+//     [{"re": 0, "im": 0}, {"r": 1, "theta": 0.785}, ...],
+//     ...
+//   ]
+// }
+//
+// Example (TOML):
+// rules = [
+//   [{re = 1, im = 0}, {re = 0, im = 0}, ...],
+//   [{re = 0, im = 0}, {r = 1, theta = 0.785}, ...],
+//   ...
+// ]
+//
+// Instead of a single matrix, a rules file can contain a list of matrices
+// in a rules_cycle attribute, the matrix rules_cycle[n % rules_cycle.len()]
+// being applied on step n (e.g. a different matrix on even and odd steps)
+//
+// Example (JSON):
+// {
+//   "rules_cycle": [
+//     [[{"re": 1, "im": 0}, ...], ...],
+//     [[{"re": 0, "im": 0}, ...], ...]
+//   ]
+// }
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RulesFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rules: Option<RulesFileMatrix>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules_cycle: Vec<RulesFileMatrix>,
+}
+
+pub type RulesFileMatrix = [[RuleEntry; 16]; 16];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum RuleEntry {
+    Cartesian { re: f64, im: f64 },
+    Polar { r: f64, theta: f64 },
+}
+
+impl From<RuleEntry> for Complex<f64> {
+    fn from(entry: RuleEntry) -> Self {
+        match entry {
+            RuleEntry::Cartesian { re, im } => Complex::new(re, im),
+            RuleEntry::Polar { r, theta } => Complex::from_polar(r, theta),
+        }
+    }
+}
+
+impl From<Complex<f64>> for RuleEntry {
+    fn from(c: Complex<f64>) -> Self {
+        RuleEntry::Cartesian { re: c.re, im: c.im }
+    }
+}
+
+// The rules of a rules file are returned as a cycle of rules,
+// with a unique element if the file contains a single matrix
+impl TryFrom<RulesFile> for Vec<Rules> {
+    type Error = RulesError;
+
+    fn try_from(rules_file: RulesFile) -> Result<Self, Self::Error> {
+        let to_rules =
+            |matrix: RulesFileMatrix| -> Rules { matrix.map(|row| row.map(Complex::from)) };
+
+        match (rules_file.rules, rules_file.rules_cycle.is_empty()) {
+            (Some(matrix), true) => Ok(vec![to_rules(matrix)]),
+            (None, false) => Ok(rules_file.rules_cycle.into_iter().map(to_rules).collect()),
+            (None, true) => Err(RulesError::Empty),
+            (Some(_), false) => Err(RulesError::Ambiguous),
+        }
+    }
+}
+
+impl From<&[Rules]> for RulesFile {
+    fn from(rules: &[Rules]) -> Self {
+        let to_matrix =
+            |rules: &Rules| -> RulesFileMatrix { rules.map(|row| row.map(RuleEntry::from)) };
+
+        match rules {
+            [rules] => RulesFile {
+                rules: Some(to_matrix(rules)),
+                rules_cycle: Vec::new(),
+            },
+            _ => RulesFile {
+                rules: None,
+                rules_cycle: rules.iter().map(to_matrix).collect(),
+            },
+        }
+    }
+}
+
+pub fn rules_from_json_str(content: &str) -> Result<Vec<Rules>, Error> {
+    let rules_file: RulesFile = serde_json::from_str(content)?;
+    Ok(rules_file.try_into()?)
+}
+
+pub fn rules_from_toml_str(content: &str) -> Result<Vec<Rules>, Error> {
+    let rules_file: RulesFile = toml::from_str(content)?;
+    Ok(rules_file.try_into()?)
+}
+
+// The rules are rejected with a RulesError if they are not a unitary matrix
+//
+// The column n of the rules contains the amplitudes of the square states
+// reached from the square state n, and the row n contains the amplitudes
+// with which each square state reaches the square state n
+#[derive(Clone, Debug, PartialEq)]
+pub enum RulesError {
+    // A universe needs at least one matrix of rules
+    Empty,
+    // A rules file contains both a rules and a rules_cycle attribute
+    Ambiguous,
+    // The rules at the given index of a cycle of rules are invalid
+    InCycle {
+        index: usize,
+        error: Box<RulesError>,
+    },
+    // An entry of the rules is NaN or infinite
+    NonFinite {
+        row: usize,
+        column: usize,
+    },
+    // The conjugate transpose of the rules times the rules
+    // is not the identity within the tolerance
+    NotUnitary {
+        tolerance: f64,
+        // Columns (and their squared norm) whose squared norm is not 1
+        columns: Vec<(usize, f64)>,
+        // Rows (and their squared norm) whose squared norm is not 1
+        rows: Vec<(usize, f64)>,
+        // Pairs of columns (and the norm of their inner product) that are not orthogonal
+        non_orthogonal_columns: Vec<(usize, usize, f64)>,
+    },
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RulesError::Empty => write!(f, "no rules were provided"),
+            RulesError::Ambiguous => write!(
+                f,
+                "a rules file must contain either a rules or a rules_cycle attribute, not both"
+            ),
+            RulesError::InCycle { index, error } => {
+                write!(f, "rules {index} of the cycle: {error}")
+            }
+            RulesError::NonFinite { row, column } => {
+                write!(f, "rules entry at row {row}, column {column} is not finite")
+            }
+            RulesError::NotUnitary {
+                tolerance,
+                columns,
+                rows,
+                non_orthogonal_columns,
+            } => {
+                write!(f, "rules are not unitary (tolerance {tolerance:e})")?;
+                for (column, norm_sqr) in columns {
+                    write!(
+                        f,
+                        "\n  column {column} (square state {:?}) has a squared norm of {norm_sqr} \
+                         instead of 1 (deviation {:e}): the amplitudes reached from this square \
+                         state don't sum to a probability of 1",
+                        index_to_square_state(*column as i32),
+                        norm_sqr - 1.
+                    )?;
+                }
+                for (row, norm_sqr) in rows {
+                    write!(
+                        f,
+                        "\n  row {row} (square state {:?}) has a squared norm of {norm_sqr} \
+                         instead of 1 (deviation {:e})",
+                        index_to_square_state(*row as i32),
+                        norm_sqr - 1.
+                    )?;
+                }
+                for (column_a, column_b, norm) in non_orthogonal_columns {
+                    write!(
+                        f,
+                        "\n  columns {column_a} and {column_b} are not orthogonal \
+                         (norm of their inner product is {norm:e})"
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+// validate_rules checks that the rules are a unitary matrix (U†U = I)
+//
+// Unitary rules guarantee that the sum of the probabilities
+// of the configurations is preserved on each step
+pub fn validate_rules(rules: &Rules, tolerance: f64) -> Result<(), RulesError> {
+    for (ri, row) in rules.iter().enumerate() {
+        for (ci, entry) in row.iter().enumerate() {
+            if !entry.is_finite() {
+                return Err(RulesError::NonFinite {
+                    row: ri,
+                    column: ci,
+                });
+            }
+        }
+    }
+
+    let len = rules.len();
+    let mut columns = Vec::new();
+    let mut rows = Vec::new();
+    let mut non_orthogonal_columns = Vec::new();
+
+    for a in 0..len {
+        // Entry (a, b) of U†U is the inner product of the columns a and b
+        for b in a..len {
+            let product: Complex<f64> = (0..len).map(|k| rules[k][a].conj() * rules[k][b]).sum();
+            if a == b {
+                if (product.re - 1.).abs() > tolerance {
+                    columns.push((a, product.re));
+                }
+            } else if product.norm() > tolerance {
+                non_orthogonal_columns.push((a, b, product.norm()));
+            }
+        }
+
+        let row_norm_sqr: f64 = rules[a].iter().map(|entry| entry.norm_sqr()).sum();
+        if (row_norm_sqr - 1.).abs() > tolerance {
+            rows.push((a, row_norm_sqr));
+        }
+    }
+
+    if columns.is_empty() && rows.is_empty() && non_orthogonal_columns.is_empty() {
+        Ok(())
+    } else {
+        Err(RulesError::NotUnitary {
+            tolerance,
+            columns,
+            rows,
+            non_orthogonal_columns,
+        })
+    }
+}
+
+// validate_rules_cycle checks that there is at least one matrix of rules
+// and that each of them is unitary
+pub fn validate_rules_cycle(rules_cycle: &[Rules], tolerance: f64) -> Result<(), RulesError> {
+    match rules_cycle {
+        [] => Err(RulesError::Empty),
+        [rules] => validate_rules(rules, tolerance),
+        _ => {
+            for (index, rules) in rules_cycle.iter().enumerate() {
+                validate_rules(rules, tolerance).map_err(|error| RulesError::InCycle {
+                    index,
+                    error: Box::new(error),
+                })?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// adjoint returns the conjugate transpose of the rules,
+// which is their inverse since the rules are unitary
+pub fn adjoint(rules: &Rules) -> Rules {
+    let mut adjoint = *rules;
+    for (ri, row) in adjoint.iter_mut().enumerate() {
+        for (ci, entry) in row.iter_mut().enumerate() {
+            *entry = rules[ci][ri].conj();
+        }
+    }
+    adjoint
+}
+
+// get_default_rules returns the rules used when no rules file is provided
+pub fn get_default_rules() -> Rules {
+    rules_from_json_str(DEFAULT_RULES).expect("the default rules file should be valid")[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::rules;
+    use num::complex::Complex;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_rule_entry() {
+        struct Test {
+            content: &'static str,
+            exp: Complex<f64>,
+        }
+
+        let tests = [
+            Test {
+                content: r#"{"re": 1, "im": 0}"#,
+                exp: Complex::new(1., 0.),
+            },
+            Test {
+                content: r#"{"re": -0.5, "im": 0.25}"#,
+                exp: Complex::new(-0.5, 0.25),
+            },
+            Test {
+                content: r#"{"r": 1, "theta": 0}"#,
+                exp: Complex::new(1., 0.),
+            },
+            Test {
+                content: r#"{"r": 2, "theta": 3.141592653589793}"#,
+                exp: Complex::from_polar(2., PI),
+            },
+        ];
+
+        for t in tests {
+            let entry: rules::RuleEntry = serde_json::from_str(t.content).unwrap();
+            let got: Complex<f64> = entry.into();
+            assert_eq!(got, t.exp);
+        }
+    }
+
+    #[test]
+    fn test_get_default_rules() {
+        let rules = rules::get_default_rules();
+
+        assert_eq!(rules[0][0], Complex::new(1., 0.));
+        assert_eq!(rules[1][4], Complex::new(1., 0.));
+        assert_eq!(rules[5][7], Complex::new(0., PI / 4.0).exp());
+        assert_eq!(rules[9][9], Complex::new(-1. / 2.0_f64.sqrt(), 0.));
+        assert_eq!(rules[15][15], Complex::new(0., PI / 2.0).exp());
+        assert_eq!(rules[0][1], Complex::new(0., 0.));
+    }
+
+    #[test]
+    fn test_validate_rules() {
+        let default_rules = rules::get_default_rules();
+        assert_eq!(
+            rules::validate_rules(&default_rules, rules::UNITARITY_TOLERANCE),
+            Ok(())
+        );
+
+        // The square state 0 is sent to both square states 0 and 1
+        // and nothing reaches the square state 2
+        let mut not_unitary = default_rules;
+        not_unitary[1][0] = Complex::new(1., 0.);
+        not_unitary[2][1] = Complex::new(0., 0.);
+        match rules::validate_rules(&not_unitary, rules::UNITARITY_TOLERANCE) {
+            Err(rules::RulesError::NotUnitary {
+                columns,
+                rows,
+                non_orthogonal_columns,
+                ..
+            }) => {
+                assert_eq!(columns, vec![(0, 2.), (1, 0.)]);
+                assert_eq!(rows, vec![(1, 2.), (2, 0.)]);
+                assert_eq!(non_orthogonal_columns, vec![(0, 4, 1.)]);
+            }
+            got => panic!("unexpected validation result: {got:?}"),
+        }
+
+        let mut non_finite = default_rules;
+        non_finite[3][5] = Complex::new(f64::NAN, 0.);
+        assert_eq!(
+            rules::validate_rules(&non_finite, rules::UNITARITY_TOLERANCE),
+            Err(rules::RulesError::NonFinite { row: 3, column: 5 })
+        );
+    }
+
+    #[test]
+    fn test_rules_from_toml_str() {
+        let row = |i: usize| -> String {
+            let entries: Vec<String> = (0..16)
+                .map(|j| {
+                    if i == j {
+                        "{r = 1, theta = 0}".to_string()
+                    } else {
+                        "{re = 0, im = 0}".to_string()
+                    }
+                })
+                .collect();
+            format!("[{}]", entries.join(", "))
+        };
+        let rows: Vec<String> = (0..16).map(row).collect();
+        let content = format!("rules = [{}]", rows.join(",\n"));
+
+        let rules = rules::rules_from_toml_str(&content).unwrap()[0];
+        for (i, row) in rules.iter().enumerate() {
+            for (j, entry) in row.iter().enumerate() {
+                let exp = if i == j { 1. } else { 0. };
+                assert_eq!(*entry, Complex::new(exp, 0.));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rules_from_json_str_invalid() {
+        let content = r#"{"rules": [[{"re": 1, "im": 0}]]}"#;
+        assert!(rules::rules_from_json_str(content).is_err());
+
+        let content = r#"{}"#;
+        assert!(rules::rules_from_json_str(content).is_err());
+    }
+
+    #[test]
+    fn test_rules_cycle() {
+        let default_rules = rules::get_default_rules();
+        let mut not_unitary = default_rules;
+        not_unitary[0][0] = Complex::new(2., 0.);
+
+        let rules_cycle = vec![default_rules, not_unitary];
+        let rules_file = rules::RulesFile::from(rules_cycle.as_slice());
+        let content = serde_json::to_string(&rules_file).unwrap();
+        let got = rules::rules_from_json_str(&content).unwrap();
+        assert_eq!(got.len(), rules_cycle.len());
+        for (got_rules, exp_rules) in got.iter().zip(rules_cycle.iter()) {
+            for (got_row, exp_row) in got_rules.iter().zip(exp_rules.iter()) {
+                for (got_entry, exp_entry) in got_row.iter().zip(exp_row.iter()) {
+                    assert!((got_entry - exp_entry).norm() < 1e-12);
+                }
+            }
+        }
+
+        assert!(matches!(
+            rules::validate_rules_cycle(&rules_cycle, rules::UNITARITY_TOLERANCE),
+            Err(rules::RulesError::InCycle { index: 1, .. })
+        ));
+        assert_eq!(
+            rules::validate_rules_cycle(&[], rules::UNITARITY_TOLERANCE),
+            Err(rules::RulesError::Empty)
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use num::complex::Complex;
+use num::{One, Zero};
+
+use super::rules::{self, RulesError};
+use super::step::square_state_to_index;
+use super::types::Rules;
+
+// RulesBuilder composes rules from simple unitary operations
+// applied one after the other on the square states:
+//     - a permutation of the square states
+//     - a phase applied on a square state
+//     - a small unitary block mixing a few square states
+//
+// Since a product of unitary matrices is unitary, the built rules are
+// guaranteed to be unitary once each operation has been validated
+//
+// Example:
+//     The default rules are a permutation of the square states,
+//     with a phase on 3 square states and a Hadamard block mixing 2 square states
+//
+//     let h = Complex::new(1. / 2.0_f64.sqrt(), 0.);
+//     let rules = RulesBuilder::new()
+//         .permutation(&[
+//             ([false, true, false, false], [false, false, false, true]),
+//             ...
+//         ])
+//         .phase([false, true, false, true], PI / 4.0)
+//         ...
+//         .block(
+//             [[false, true, true, false], [true, false, false, true]],
+//             [[h, h], [h, -h]],
+//         )
+//         .build()?;
+#[derive(Clone, Debug, Default)]
+pub struct RulesBuilder {
+    operations: Vec<Rules>,
+}
+
+impl RulesBuilder {
+    // The rules of a new builder are the identity
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Each (from, to) pair of the mapping sends the square state `from` to the square state `to`
+    // The square states that are not in the mapping are left unchanged
+    pub fn permutation(mut self, mapping: &[([bool; 4], [bool; 4])]) -> Self {
+        let mut operation = identity();
+        for (from, _) in mapping.iter() {
+            let from = square_state_to_index(*from) as usize;
+            operation[from][from] = Complex::zero();
+        }
+        for (from, to) in mapping.iter() {
+            let from = square_state_to_index(*from) as usize;
+            let to = square_state_to_index(*to) as usize;
+            operation[to][from] = Complex::one();
+        }
+
+        self.operations.push(operation);
+        self
+    }
+
+    // Multiply the amplitude of the square state by e^(i * theta)
+    pub fn phase(mut self, square_state: [bool; 4], theta: f64) -> Self {
+        let mut operation = identity();
+        let index = square_state_to_index(square_state) as usize;
+        operation[index][index] = Complex::from_polar(1., theta);
+
+        self.operations.push(operation);
+        self
+    }
+
+    // Embed a N*N block acting on N square states, the entry block[i][j]
+    // is the amplitude with which square_states[j] is sent to square_states[i]
+    //
+    // Panics if a square state appears several times in square_states
+    pub fn block<const N: usize>(
+        mut self,
+        square_states: [[bool; 4]; N],
+        block: [[Complex<f64>; N]; N],
+    ) -> Self {
+        let indexes =
+            square_states.map(|square_state| square_state_to_index(square_state) as usize);
+        for (i, index) in indexes.iter().enumerate() {
+            assert!(
+                !indexes[..i].contains(index),
+                "square state {:?} appears several times in the block",
+                square_states[i]
+            );
+        }
+
+        let mut operation = identity();
+        for (i, row_index) in indexes.iter().enumerate() {
+            for (j, column_index) in indexes.iter().enumerate() {
+                operation[*row_index][*column_index] = block[i][j];
+            }
+        }
+
+        self.operations.push(operation);
+        self
+    }
+
+    // build validates each operation and returns their product,
+    // the first operation added being the first one applied
+    pub fn build(self) -> Result<Rules, RulesError> {
+        let mut rules = identity();
+        for operation in self.operations.iter() {
+            rules::validate_rules(operation, rules::UNITARITY_TOLERANCE)?;
+            rules = multiply(operation, &rules);
+        }
+
+        Ok(rules)
+    }
+}
+
+fn identity() -> Rules {
+    let mut rules = [[Complex::zero(); 16]; 16];
+    for (i, row) in rules.iter_mut().enumerate() {
+        row[i] = Complex::one();
+    }
+    rules
+}
+
+fn multiply(a: &Rules, b: &Rules) -> Rules {
+    let mut product = [[Complex::zero(); 16]; 16];
+    for (i, row) in product.iter_mut().enumerate() {
+        for (j, entry) in row.iter_mut().enumerate() {
+            *entry = (0..16).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    product
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::rules::{self, RulesError};
+    use crate::universe::rules_builder::RulesBuilder;
+    use crate::universe::step;
+    use num::complex::Complex;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_build_default_rules() {
+        let s = step::index_to_square_state;
+        let h = Complex::new(1. / 2.0_f64.sqrt(), 0.);
+
+        let got = RulesBuilder::new()
+            .permutation(&[
+                (s(4), s(1)),
+                (s(1), s(2)),
+                (s(11), s(3)),
+                (s(8), s(4)),
+                (s(7), s(5)),
+                (s(5), s(7)),
+                (s(2), s(8)),
+                (s(14), s(10)),
+                (s(3), s(11)),
+                (s(13), s(12)),
+                (s(12), s(13)),
+                (s(10), s(14)),
+            ])
+            .phase(s(5), PI / 4.0)
+            .phase(s(10), PI / 8.0)
+            .phase(s(15), PI / 2.0)
+            .block([s(6), s(9)], [[h, h], [h, -h]])
+            .build()
+            .unwrap();
+
+        let exp = rules::get_default_rules();
+        for i in 0..16 {
+            for j in 0..16 {
+                assert!((got[i][j] - exp[i][j]).norm() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_not_unitary() {
+        let s = step::index_to_square_state;
+        let one = Complex::new(1., 0.);
+
+        // Two square states are sent to the same square state
+        let got = RulesBuilder::new()
+            .permutation(&[(s(1), s(2)), (s(3), s(2))])
+            .build();
+        assert!(matches!(got, Err(RulesError::NotUnitary { .. })));
+
+        let got = RulesBuilder::new()
+            .block([s(1), s(2)], [[one, one], [one, one]])
+            .build();
+        assert!(matches!(got, Err(RulesError::NotUnitary { .. })));
+    }
+}
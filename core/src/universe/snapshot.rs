@@ -0,0 +1,220 @@
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use super::files;
+use super::pruning::PruningPolicy;
+use super::rules::{self, RulesFile};
+use super::topology::Topology;
+use super::types::*;
+use crate::Error;
+
+// Version of the snapshot format written by `Universe::save`
+//
+// Version 2 adds the topology, snapshots of version 1 are loaded
+// with the default topology (an infinite plane)
+// Version 3 adds the pruning policy, older snapshots are loaded with the default policy
+// Version 4 adds the tolerance of the strict mode, older snapshots are loaded
+// without strict mode
+pub const SNAPSHOT_VERSION: u32 = 4;
+
+// A snapshot contains everything needed to resume a universe
+// where it was saved, the combined state is computed again on load
+//
+// Example:
+// {
+//   "version": 4,
+//   "state": [{"amplitude": {"re": 1, "im": 0}, "living_cells": [...]}],
+//   "is_even_step": false,
+//   "step_count": 3,
+//   "rules": [[{"re": 1, "im": 0}, ...], ...],
+//   "topology": {"type": "torus", "width": 20, "height": 20},
+//   "pruning": {"strategy": {"type": "absolute", "threshold": 1e-6}, "renormalize": false},
+//   "probability_tolerance": 1e-9,
+//   "rng": {...}
+// }
+//
+// The rules are written as in a rules file (see `rules::RulesFile`)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    pub version: u32,
+    pub state: State,
+    pub is_even_step: bool,
+    pub step_count: usize,
+    #[serde(flatten)]
+    pub rules: RulesFile,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub pruning: PruningPolicy,
+    #[serde(default)]
+    pub probability_tolerance: Option<f64>,
+    pub rng: ChaCha8Rng,
+}
+
+impl Universe {
+    pub fn to_snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            state: self.state.clone(),
+            is_even_step: self.is_even_step,
+            step_count: self.step_count,
+            rules: RulesFile::from(self.rules.as_slice()),
+            topology: self.topology,
+            pruning: self.pruning,
+            probability_tolerance: self.probability_tolerance,
+            rng: self.rng.clone(),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot) -> Result<Self, Error> {
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(Error::UnsupportedSnapshotVersion {
+                version: snapshot.version,
+                supported: SNAPSHOT_VERSION,
+            });
+        }
+
+        let mut universe = Self::new_from_state(snapshot.state, snapshot.rules.try_into()?)?
+            .with_topology(snapshot.topology)?
+            .with_pruning(snapshot.pruning)?;
+        universe.is_even_step = snapshot.is_even_step;
+        universe.step_count = snapshot.step_count;
+        universe.probability_tolerance = snapshot.probability_tolerance;
+        universe.rng = snapshot.rng;
+        Ok(universe)
+    }
+
+    // save writes a snapshot of the universe in a JSON file
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        files::write_snapshot_to_file(path, &self.to_snapshot())
+    }
+
+    // load reads a universe from a snapshot file written by `save`
+    // or from a state file, the universe then starts at step 0 with the default rules
+    pub fn load(path: &str) -> Result<Self, Error> {
+        Self::load_with(path, LoadOptions::default())
+    }
+
+    pub fn load_with(path: &str, options: LoadOptions) -> Result<Self, Error> {
+        let universe = match files::get_snapshot_from_file(path)? {
+            SnapshotFile::Snapshot(snapshot) => Self::from_snapshot(*snapshot)?,
+            SnapshotFile::State(state) => {
+                Self::new_from_state(state, vec![rules::get_default_rules()])?
+            }
+        };
+        universe.with_load_options(options)
+    }
+}
+
+// A file read by `Universe::load` contains either a snapshot or a state
+pub enum SnapshotFile {
+    Snapshot(Box<Snapshot>),
+    State(State),
+}
+
+// A state is a JSON array while a snapshot is a JSON object,
+// so the first character of the content is enough to know what it contains
+pub fn snapshot_file_from_str(content: &str) -> Result<SnapshotFile, Error> {
+    if content.trim_start().starts_with('[') {
+        Ok(SnapshotFile::State(serde_json::from_str(content)?))
+    } else {
+        Ok(SnapshotFile::Snapshot(Box::new(serde_json::from_str(
+            content,
+        )?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::pruning::{PruningPolicy, PruningStrategy};
+    use crate::universe::rules_builder::RulesBuilder;
+    use crate::universe::topology::Topology;
+    use crate::universe::types::Universe;
+    use crate::universe::{rules, snapshot};
+    use crate::Error;
+
+    #[test]
+    fn test_save_load() {
+        let identity = RulesBuilder::new().build().unwrap();
+        let torus = Topology::Torus {
+            width: 20,
+            height: 20,
+        };
+        let mut universe = Universe::new_from_files("fixtures/state_grid_20.json", None)
+            .unwrap()
+            .with_topology(torus)
+            .unwrap()
+            .with_pruning(PruningPolicy {
+                strategy: PruningStrategy::TopK { k: 50 },
+                renormalize: true,
+            })
+            .unwrap()
+            .with_strict_mode(1e-9)
+            .with_seed(3);
+        universe.rules = vec![rules::get_default_rules(), identity];
+        for _ in 0..7 {
+            universe.step().unwrap();
+        }
+
+        let path = std::env::temp_dir().join("qautomata_test_save_load.json");
+        let path = path.to_str().unwrap();
+        universe.save(path).unwrap();
+        let mut loaded = Universe::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.state, universe.state);
+        assert_eq!(loaded.combined_state, universe.combined_state);
+        assert_eq!(loaded.is_even_step, universe.is_even_step);
+        assert_eq!(loaded.topology, universe.topology);
+        assert_eq!(loaded.pruning, universe.pruning);
+        assert_eq!(loaded.probability_tolerance, universe.probability_tolerance);
+        assert_eq!(loaded.step_count, universe.step_count);
+        assert_eq!(loaded.rules, universe.rules);
+
+        // The loaded universe resumes with the same measures
+        for _ in 0..10 {
+            universe.step().unwrap();
+            loaded.step().unwrap();
+        }
+        universe.measure().unwrap();
+        loaded.measure().unwrap();
+        assert_eq!(loaded.state, universe.state);
+    }
+
+    #[test]
+    fn test_load_state_file() {
+        let universe = Universe::load("fixtures/state_2_adjacent_cells.json").unwrap();
+        let exp = Universe::new_from_files("fixtures/state_2_adjacent_cells.json", None).unwrap();
+
+        assert_eq!(universe.state, exp.state);
+        assert_eq!(universe.step_count, 0);
+        assert!(universe.is_even_step);
+        assert_eq!(universe.rules, exp.rules);
+    }
+
+    #[test]
+    fn test_load_version_3() {
+        // A snapshot written before the strict mode was saved is loaded without strict mode
+        let mut snapshot =
+            serde_json::to_value(Universe::new().with_strict_mode(1e-9).to_snapshot()).unwrap();
+        snapshot["version"] = 3.into();
+        snapshot
+            .as_object_mut()
+            .unwrap()
+            .remove("probability_tolerance");
+        let snapshot = serde_json::from_value(snapshot).unwrap();
+
+        let universe = Universe::from_snapshot(snapshot).unwrap();
+        assert_eq!(universe.probability_tolerance, None);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut snapshot = Universe::new().to_snapshot();
+        snapshot.version = snapshot::SNAPSHOT_VERSION + 1;
+        assert!(matches!(
+            Universe::from_snapshot(snapshot),
+            Err(Error::UnsupportedSnapshotVersion { .. })
+        ));
+    }
+}
@@ -1,56 +1,310 @@
 use num::complex::Complex;
-use std::collections::HashMap;
-
+use num::Zero;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use super::history::{HistoryEdge, HistoryEvent};
+use super::living_cells::LivingCells;
+use super::pruning::PruningReport;
+use super::rules;
+use super::topology::{Boundary, Topology};
 use super::types::*;
+use crate::Error;
+
+// Report of a step
+//
+// - norm_before and norm_after are the total probabilities before and after the step
+// - pruned contains the configurations discarded by the pruning policy after the interferences
+// - splits is the number of configurations added by the squares with several new states
+// - merges is the number of configurations merged into another one by the interferences
+// - drift is the change of the total probability that is not explained by the pruning,
+//   it is only caused by rounding errors (or by an absorbing boundary)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StepReport {
+    pub norm_before: f64,
+    pub norm_after: f64,
+    pub pruned: PruningReport,
+    pub splits: usize,
+    pub merges: usize,
+    pub drift: f64,
+}
+
+// The branching of a configuration during a step: the number of configurations
+// it was split into and the top left cells of the squares in which it split
+pub(crate) struct Branching {
+    configurations: usize,
+    split_squares: Vec<Coordinates>,
+}
 
-impl Universe {
-    pub fn step(&mut self) {
+impl<C: LivingCells> Universe<C> {
+    pub fn step(&mut self) -> Result<StepReport, Error> {
+        let rules = *self.current_rules()?;
         self.step_count += 1;
+        let report = self.apply_rules(rules, HistoryEvent::Step);
+        self.is_even_step = !self.is_even_step;
+        report
+    }
+
+    // step_back reverts the last step by applying the adjoint (conjugate transpose)
+    // of its rules on the same 2 * 2 squares
+    //
+    // Since the rules are unitary, their adjoint is their inverse, so the state is
+    // restored exactly, as long as no configuration was measured or pruned
+    // since the step
+    //
+    // Nothing happens if the universe is at its first step
+    pub fn step_back(&mut self) -> Result<StepReport, Error> {
+        if self.step_count == 0 {
+            let norm = self.total_probability();
+            return Ok(StepReport {
+                norm_before: norm,
+                norm_after: norm,
+                ..Default::default()
+            });
+        }
+
+        self.step_count -= 1;
+        let rules = match self.current_rules() {
+            Ok(rules) => rules::adjoint(rules),
+            Err(err) => {
+                self.step_count += 1;
+                return Err(err);
+            }
+        };
+        self.is_even_step = !self.is_even_step;
+        self.apply_rules(rules, HistoryEvent::StepBack)
+    }
+
+    // apply_rules computes the new state of the universe by applying the rules
+    // on the 2 * 2 squares of the current step parity
+    //
+    // In strict mode, an error is returned if the total probability drifted,
+    // the new state is kept in this case
+    //
+    // If the history is recorded, the new state is added to it with the given event
+    fn apply_rules(&mut self, rules: Rules, event: HistoryEvent) -> Result<StepReport, Error> {
         let old_state_len = self.state.len();
-        let mut new_state: State = State::new();
+        let mut report = StepReport {
+            norm_before: self.total_probability(),
+            ..Default::default()
+        };
+        let old_amplitudes = self.amplitudes_for_history();
+        let (new_state, new_combined_state, branchings) = self.step_configurations(rules);
+
+        self.state = new_state;
+        self.combined_state = new_combined_state;
+        report.splits = self.state.len() - old_state_len;
+        let stepped_amplitudes = self.amplitudes_for_history();
+
+        // Interferences can happen only if new superpositions are created during the step,
+        // or if cells disappear on an absorbing boundary, which can make configurations equal
+        //
+        // indexes contains the index of each stepped configuration in the new state,
+        // the index of the configuration it merged into, or None if it was cancelled or pruned,
+        // and the amplitudes of the kept configurations are multiplied by renormalization
+        let absorbing = self.topology.boundary() == Some(Boundary::Absorbing);
+        let (indexes, renormalization) = if self.state.len() > old_state_len || absorbing {
+            let stepped_state_len = self.state.len();
+            let (pruned, indexes, renormalization) = self.solve_interference_with_indexes()?;
+            report.pruned = pruned;
+            report.merges = stepped_state_len - self.state.len() - report.pruned.configurations;
+            (indexes, renormalization)
+        } else {
+            // The pruning policy applies on every step, e.g. for TopK to be enforced
+            // on a loaded state larger than k
+            let (pruned, kept, renormalization) = self.prune_with_kept();
+            report.pruned = pruned;
+            let mut index = 0;
+            let indexes = kept
+                .into_iter()
+                .map(|kept| {
+                    kept.then(|| {
+                        index += 1;
+                        index - 1
+                    })
+                })
+                .collect();
+            (indexes, renormalization)
+        };
+
+        if let (Some(old_amplitudes), Some(stepped_amplitudes)) =
+            (old_amplitudes, stepped_amplitudes)
+        {
+            let mut parents: Vec<Vec<HistoryEdge>> = vec![Vec::new(); self.state.len()];
+            let mut stepped_index = 0;
+            for (parent, branching) in branchings.iter().enumerate() {
+                for _ in 0..branching.configurations {
+                    // A parent without amplitude contributes nothing to its children,
+                    // and the factor of its edges can't be computed
+                    if let (Some(index), false) =
+                        (indexes[stepped_index], old_amplitudes[parent].is_zero())
+                    {
+                        parents[index].push(HistoryEdge {
+                            parent,
+                            factor: stepped_amplitudes[stepped_index] / old_amplitudes[parent]
+                                * renormalization,
+                            split_squares: branching.split_squares.clone(),
+                        });
+                    }
+                    stepped_index += 1;
+                }
+            }
+            self.record_history(event, parents);
+        }
+
+        // The renormalization restores the pruned probability
+        report.norm_after = self.total_probability();
+        report.drift = report.norm_after - report.norm_before;
+        if !self.pruning.renormalize {
+            report.drift += report.pruned.probability;
+        }
+
+        match self.probability_tolerance {
+            Some(tolerance) if report.drift.abs() > tolerance => Err(Error::ProbabilityDrift {
+                step: self.step_count,
+                drift: report.drift,
+                tolerance,
+            }),
+            _ => Ok(report),
+        }
+    }
+
+    // step_configurations returns the new state, the new combined state
+    // and the branching of each configuration, whose new configurations
+    // are consecutive in the new state
+    #[cfg(not(feature = "parallel"))]
+    fn step_configurations(
+        &self,
+        rules: Rules,
+    ) -> (State<C>, HashMap<Coordinates, f64>, Vec<Branching>) {
+        let mut new_state: State<C> = State::new();
         let mut new_combined_state: HashMap<Coordinates, f64> = HashMap::new();
+        let mut branchings: Vec<Branching> = Vec::with_capacity(self.state.len());
 
-        for configuration in self.state.iter_mut() {
-            new_state.append(&mut configuration.step(
-                self.rules,
+        for configuration in self.state.iter() {
+            let mut split_squares: Vec<Coordinates> = Vec::new();
+            let mut new_configurations = configuration.step_with_splits(
+                rules,
                 self.is_even_step,
-                &mut new_combined_state,
-            ));
+                self.topology,
+                &mut |coordinates, probability| {
+                    *new_combined_state.entry(coordinates.clone()).or_insert(0.0) += probability;
+                },
+                &mut split_squares,
+            );
+            branchings.push(Branching {
+                configurations: new_configurations.len(),
+                split_squares,
+            });
+            new_state.append(&mut new_configurations);
         }
 
-        self.state = new_state;
-        self.combined_state = new_combined_state;
-        self.is_even_step = !self.is_even_step;
+        (new_state, new_combined_state, branchings)
+    }
 
-        // Interferences can happen only if new superpositions are created during the step
-        if self.state.len() > old_state_len {
-            self.solve_interference();
+    // The configurations are stepped in parallel, each one recording the probabilities
+    // it adds to the combined state, which are then added in the order of the configurations
+    // so that the new state and combined state are exactly the ones computed sequentially
+    #[cfg(feature = "parallel")]
+    fn step_configurations(
+        &self,
+        rules: Rules,
+    ) -> (State<C>, HashMap<Coordinates, f64>, Vec<Branching>) {
+        let is_even_step = self.is_even_step;
+        let topology = self.topology;
+        let steps: Vec<_> = self
+            .state
+            .par_iter()
+            .map(|configuration| {
+                let mut probabilities: Vec<(Coordinates, f64)> = Vec::new();
+                let mut split_squares: Vec<Coordinates> = Vec::new();
+                let new_configurations = configuration.step_with_splits(
+                    rules,
+                    is_even_step,
+                    topology,
+                    &mut |coordinates, probability| {
+                        probabilities.push((coordinates.clone(), probability))
+                    },
+                    &mut split_squares,
+                );
+                (new_configurations, probabilities, split_squares)
+            })
+            .collect();
+
+        let mut new_state: State<C> = State::new();
+        let mut new_combined_state: HashMap<Coordinates, f64> = HashMap::new();
+        let mut branchings: Vec<Branching> = Vec::with_capacity(steps.len());
+        for (mut new_configurations, probabilities, split_squares) in steps {
+            branchings.push(Branching {
+                configurations: new_configurations.len(),
+                split_squares,
+            });
+            new_state.append(&mut new_configurations);
+            for (coordinates, probability) in probabilities {
+                *new_combined_state.entry(coordinates).or_insert(0.0) += probability;
+            }
         }
+
+        (new_state, new_combined_state, branchings)
     }
 }
 
-impl Configuration {
+impl<C: LivingCells> Configuration<C> {
     pub fn step(
-        &mut self,
+        &self,
         rules: Rules,
         is_even_step: bool,
+        topology: Topology,
         new_combined_state: &mut HashMap<Coordinates, f64>,
-    ) -> Vec<Configuration> {
-        let mut new_configurations: Vec<Configuration> = vec![Configuration {
-            amplitude: self.amplitude,
-            living_cells: HashMap::new(),
-        }];
-
-        for coordinates in self
-            .living_cells
-            .keys()
-            .cloned()
-            .collect::<Vec<Coordinates>>()
-        {
-            if self.living_cells.get(&coordinates) == Some(&true) {
-                continue;
-            }
+    ) -> Vec<Configuration<C>> {
+        self.step_with(
+            rules,
+            is_even_step,
+            topology,
+            &mut |coordinates, probability| {
+                *new_combined_state.entry(coordinates.clone()).or_insert(0.0) += probability;
+            },
+        )
+    }
+
+    // step_with computes the new configurations like `step`, calling add_probability
+    // with each cell added to a new configuration and the probability it adds to the combined state
+    pub fn step_with<F: FnMut(&Coordinates, f64)>(
+        &self,
+        rules: Rules,
+        is_even_step: bool,
+        topology: Topology,
+        add_probability: &mut F,
+    ) -> Vec<Configuration<C>> {
+        self.step_with_splits(
+            rules,
+            is_even_step,
+            topology,
+            add_probability,
+            &mut Vec::new(),
+        )
+    }
 
+    // step_with_splits computes the new configurations like `step_with`, adding to split_squares
+    // the top left cell of each square having several new square states
+    pub fn step_with_splits<F: FnMut(&Coordinates, f64)>(
+        &self,
+        rules: Rules,
+        is_even_step: bool,
+        topology: Topology,
+        add_probability: &mut F,
+        split_squares: &mut Vec<Coordinates>,
+    ) -> Vec<Configuration<C>> {
+        // The living cells of the new configurations are sorted
+        // once all the squares have been computed
+        let mut new_configurations: Vec<(Complex<f64>, Vec<Coordinates>)> =
+            vec![(self.amplitude, Vec::with_capacity(self.living_cells.len()))];
+
+        // The squares already computed during this step, identified by their top left cell
+        let mut computed_squares: HashSet<Coordinates> = HashSet::new();
+
+        for coordinates in self.living_cells.cells() {
             let x_min: i32;
             let y_min: i32;
 
@@ -73,12 +327,24 @@ impl Configuration {
             // On odd steps (Step 1 above as an example), to calculate x_min (same for y_min)
             // given one of the cells [x, y] of a 2 * 2 square in which rules locally apply
             // we take the value of x if x is odd or x - 1 if x is even
+            //
+            // rem_euclid is used instead of % to get the parity of negative coordinates
+            // (-1 % 2 is -1 but -1.rem_euclid(2) is 1)
+            let x_parity = coordinates.x.rem_euclid(2);
+            let y_parity = coordinates.y.rem_euclid(2);
             if is_even_step {
-                x_min = coordinates.x - if coordinates.x % 2 == 0 { 0 } else { 1 };
-                y_min = coordinates.y - if coordinates.y % 2 == 0 { 0 } else { 1 };
+                x_min = coordinates.x - x_parity;
+                y_min = coordinates.y - y_parity;
             } else {
-                x_min = coordinates.x - if coordinates.x % 2 == 1 { 0 } else { 1 };
-                y_min = coordinates.y - if coordinates.y % 2 == 1 { 0 } else { 1 };
+                x_min = coordinates.x - (1 - x_parity);
+                y_min = coordinates.y - (1 - y_parity);
+            }
+
+            // Each square is computed once, from its first living cell
+            // (on a torus, the same square can be reached from both sides of a border)
+            let square = topology.wrap(Coordinates { x: x_min, y: y_min });
+            if !computed_squares.insert(square.clone()) {
+                continue;
             }
 
             // The square_state contains 4 bool, one for each cell of the square
@@ -89,64 +355,39 @@ impl Configuration {
             //
             // [0 1 2 3] -> square_state
             //
-            // The values are initialized at false and updated to true below
-            // when checking if the correspondings cells are alive
-            let mut square_state: [bool; 4] = [false, false, false, false];
-
-            // Get the 4 living cells of the square [0] [1]
-            //                                      [2] [3]
-            //
-            // - Mark the living cells as computed during this step
-            //    in order to not compute them again in the loop
-            // - Mark the cells alive in the `square_state` array
-            //    if we find them in the list of living cells (see comment above)
-            {
-                let living_cell_0 = self
-                    .living_cells
-                    .get_mut(&Coordinates { x: x_min, y: y_min });
-                if let Some(lc0) = living_cell_0 {
-                    *lc0 = true;
-                    square_state[0] = true;
-                }
-            }
-            {
-                let living_cell_1 = self.living_cells.get_mut(&Coordinates {
-                    x: x_min,
-                    y: y_min + 1,
-                });
-                if let Some(lc1) = living_cell_1 {
-                    *lc1 = true;
-                    square_state[1] = true;
-                }
-            }
-            {
-                let living_cell_2 = self.living_cells.get_mut(&Coordinates {
-                    x: x_min + 1,
-                    y: y_min,
-                });
-                if let Some(lc2) = living_cell_2 {
-                    *lc2 = true;
-                    square_state[2] = true;
-                }
-            }
-            {
-                let living_cell_3 = self.living_cells.get_mut(&Coordinates {
-                    x: x_min + 1,
-                    y: y_min + 1,
-                });
-                if let Some(lc3) = living_cell_3 {
-                    *lc3 = true;
-                    square_state[3] = true;
-                }
-            }
+            // The cells of the square outside the universe (see `topology::Topology`) are None
+            let square_cells = topology.square_cells(x_min, y_min);
+            let is_inner_square = square_cells[0] == Some(Coordinates { x: x_min, y: y_min })
+                && square_cells[3]
+                    == Some(Coordinates {
+                        x: x_min + 1,
+                        y: y_min + 1,
+                    });
+            let square_state: [bool; 4] = if is_inner_square {
+                self.living_cells.square_state(x_min, y_min)
+            } else {
+                square_cells.clone().map(|cell| match cell {
+                    Some(cell) => self.living_cells.contains(&cell),
+                    None => false,
+                })
+            };
 
+            // The cells of a square crossing a reflecting wall stay where they are
             let new_square_states: Vec<(Complex<f64>, [bool; 4])> =
-                compute_rules(rules, square_state);
-
-            // Think about what to do here, probably an error
+                if !is_inner_square && topology.boundary() == Some(Boundary::Reflecting) {
+                    vec![(Complex::new(1., 0.), square_state)]
+                } else {
+                    compute_rules(rules, square_state)
+                };
+
+            // The rules are validated to be unitary when the universe is created
+            // so each square state leads to at least one new square state
             if new_square_states.is_empty() {
                 continue;
             }
+            if new_square_states.len() > 1 {
+                split_squares.push(square);
+            }
 
             // For each new_configuration:
             //     - For each new_square_state except the first one:
@@ -154,139 +395,54 @@ impl Configuration {
             //           add it to new_configurations (we won't iter on it on the main
             //           loop)
             //     - Then we apply the first new_square_state to the new_configuation
-            //
-            // Apply a square_state to a configuration:
-            //     - multiply the amplitude of the configuration with the one of the square_state
-            //     - add each living_cell of the square_state in the living_cells of the
-            //       configuration
             for i in 0..new_configurations.len() {
                 for new_square_state in new_square_states.iter().skip(1) {
-                    let mut new_configuration: Configuration = new_configurations[i].clone();
-
-                    new_configuration.amplitude *= new_square_state.0;
-                    if new_square_state.1[0] {
-                        new_configuration
-                            .living_cells
-                            .insert(Coordinates { x: x_min, y: y_min }, false);
-
-                        *new_combined_state
-                            .entry(Coordinates { x: x_min, y: y_min })
-                            .or_insert(0.0) += new_configuration.amplitude.norm_sqr();
-                    }
-                    if new_square_state.1[1] {
-                        new_configuration.living_cells.insert(
-                            Coordinates {
-                                x: x_min,
-                                y: y_min + 1,
-                            },
-                            false,
-                        );
-
-                        *new_combined_state
-                            .entry(Coordinates {
-                                x: x_min,
-                                y: y_min + 1,
-                            })
-                            .or_insert(0.0) += new_configuration.amplitude.norm_sqr();
-                    }
-                    if new_square_state.1[2] {
-                        new_configuration.living_cells.insert(
-                            Coordinates {
-                                x: x_min + 1,
-                                y: y_min,
-                            },
-                            false,
-                        );
-
-                        *new_combined_state
-                            .entry(Coordinates {
-                                x: x_min + 1,
-                                y: y_min,
-                            })
-                            .or_insert(0.0) += new_configuration.amplitude.norm_sqr();
-                    }
-                    if new_square_state.1[3] {
-                        new_configuration.living_cells.insert(
-                            Coordinates {
-                                x: x_min + 1,
-                                y: y_min + 1,
-                            },
-                            false,
-                        );
-
-                        *new_combined_state
-                            .entry(Coordinates {
-                                x: x_min + 1,
-                                y: y_min + 1,
-                            })
-                            .or_insert(0.0) += new_configuration.amplitude.norm_sqr();
-                    }
-
-                    new_configurations.push(new_configuration);
-                }
-
-                new_configurations[i].amplitude *= new_square_states[0].0;
-                if new_square_states[0].1[0] {
-                    new_configurations[i]
-                        .living_cells
-                        .insert(Coordinates { x: x_min, y: y_min }, false);
-
-                    *new_combined_state
-                        .entry(Coordinates { x: x_min, y: y_min })
-                        .or_insert(0.0) += new_configurations[i].amplitude.norm_sqr();
-                }
-                if new_square_states[0].1[1] {
-                    new_configurations[i].living_cells.insert(
-                        Coordinates {
-                            x: x_min,
-                            y: y_min + 1,
-                        },
-                        false,
-                    );
-
-                    *new_combined_state
-                        .entry(Coordinates {
-                            x: x_min,
-                            y: y_min + 1,
-                        })
-                        .or_insert(0.0) += new_configurations[i].amplitude.norm_sqr();
-                }
-                if new_square_states[0].1[2] {
-                    new_configurations[i].living_cells.insert(
-                        Coordinates {
-                            x: x_min + 1,
-                            y: y_min,
-                        },
-                        false,
+                    let mut new_configuration = new_configurations[i].clone();
+                    apply_square_state(
+                        &mut new_configuration,
+                        new_square_state,
+                        &square_cells,
+                        add_probability,
                     );
-
-                    *new_combined_state
-                        .entry(Coordinates {
-                            x: x_min + 1,
-                            y: y_min,
-                        })
-                        .or_insert(0.0) += new_configurations[i].amplitude.norm_sqr();
+                    new_configurations.push(new_configuration);
                 }
-                if new_square_states[0].1[3] {
-                    new_configurations[i].living_cells.insert(
-                        Coordinates {
-                            x: x_min + 1,
-                            y: y_min + 1,
-                        },
-                        false,
-                    );
 
-                    *new_combined_state
-                        .entry(Coordinates {
-                            x: x_min + 1,
-                            y: y_min + 1,
-                        })
-                        .or_insert(0.0) += new_configurations[i].amplitude.norm_sqr();
-                }
+                apply_square_state(
+                    &mut new_configurations[i],
+                    &new_square_states[0],
+                    &square_cells,
+                    add_probability,
+                );
             }
         }
 
         new_configurations
+            .into_iter()
+            .map(|(amplitude, living_cells)| Configuration {
+                amplitude,
+                living_cells: C::from_unsorted(living_cells),
+            })
+            .collect()
+    }
+}
+
+// Apply a square_state to a configuration:
+//     - multiply the amplitude of the configuration with the one of the square_state
+//     - add each living_cell of the square_state inside the universe in the living_cells
+//       of the configuration and its probability in the combined state
+fn apply_square_state<F: FnMut(&Coordinates, f64)>(
+    configuration: &mut (Complex<f64>, Vec<Coordinates>),
+    new_square_state: &(Complex<f64>, [bool; 4]),
+    square_cells: &[Option<Coordinates>; 4],
+    add_probability: &mut F,
+) {
+    configuration.0 *= new_square_state.0;
+    for (alive, cell) in new_square_state.1.iter().zip(square_cells.iter()) {
+        // The cells brought outside the universe disappear
+        if let (true, Some(cell)) = (*alive, cell) {
+            configuration.1.push(cell.clone());
+            add_probability(cell, configuration.0.norm_sqr());
+        }
     }
 }
 
@@ -328,7 +484,7 @@ pub fn compute_rules(rules: Rules, square_state: [bool; 4]) -> Vec<(Complex<f64>
 // number = 0000 | 0100 | 0000 | 0000;
 //
 //The resulted number is number = 4 = 0100
-fn square_state_to_index(square_state: [bool; 4]) -> i32 {
+pub(crate) fn square_state_to_index(square_state: [bool; 4]) -> i32 {
     let s = [
         (square_state[0] as i32) << 3,
         (square_state[1] as i32) << 2,
@@ -340,7 +496,7 @@ fn square_state_to_index(square_state: [bool; 4]) -> i32 {
     s[0] | s[1] | s[2] | s[3]
 }
 
-fn index_to_square_state(index: i32) -> [bool; 4] {
+pub(crate) fn index_to_square_state(index: i32) -> [bool; 4] {
     [
         ((index >> 3) & 1) == 1,
         ((index >> 2) & 1) == 1,
@@ -351,12 +507,332 @@ fn index_to_square_state(index: i32) -> [bool; 4] {
 
 #[cfg(test)]
 mod tests {
-    use crate::universe::{step, types};
+    use crate::universe::cell_set::CellSet;
+    use crate::universe::pruning::{PruningPolicy, PruningStrategy};
+    use crate::universe::rules::RulesError;
+    use crate::universe::rules_builder::RulesBuilder;
+    use crate::universe::tiled_cells::TiledCells;
+    use crate::universe::topology::{Boundary, Topology};
+    use crate::universe::{rules, step, types};
+    use crate::Error;
     use num::complex::Complex;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_step_rules_cycle() {
+        let default_rules = rules::get_default_rules();
+        let identity = RulesBuilder::new().build().unwrap();
+
+        let mut universe =
+            types::Universe::new_from_files("fixtures/state_2_diagonal_cells.json", None).unwrap();
+        universe.rules = vec![default_rules, identity];
+        let mut exp = universe.clone();
+        exp.rules = vec![default_rules];
+
+        // The identity applies on odd steps so the second step changes nothing
+        universe.step().unwrap();
+        universe.step().unwrap();
+        exp.step().unwrap();
+
+        assert_eq!(universe.step_count, 2);
+        assert_eq!(universe.state, exp.state);
+        assert_eq!(universe.current_rules().unwrap(), &default_rules);
+    }
+
+    #[test]
+    fn test_step_empty_rules() {
+        let mut universe =
+            types::Universe::new_from_files("fixtures/state_2_diagonal_cells.json", None).unwrap();
+        universe.step().unwrap();
+        universe.rules = Vec::new();
+        let exp = universe.clone();
+
+        // The universe is left unchanged
+        assert!(matches!(
+            universe.step(),
+            Err(Error::InvalidRules(RulesError::Empty))
+        ));
+        assert!(matches!(
+            universe.step_back(),
+            Err(Error::InvalidRules(RulesError::Empty))
+        ));
+        assert_eq!(universe.step_count, exp.step_count);
+        assert_eq!(universe.is_even_step, exp.is_even_step);
+        assert_eq!(universe.state, exp.state);
+    }
+
+    #[test]
+    fn test_step_tiled_cells() {
+        let universe =
+            types::Universe::new_from_files("fixtures/state_grid_20.json", None).unwrap();
+        let mut tiled_universe = universe.clone().into_storage::<TiledCells>();
+        let mut universe = universe;
+
+        for _ in 0..14 {
+            universe.step().unwrap();
+            tiled_universe.step().unwrap();
+
+            // The configurations are computed in a different order with each storage
+            let sorted_state = |universe: types::Universe<CellSet>| {
+                let mut state = universe.state;
+                state.sort_by(|a, b| a.living_cells.cmp(&b.living_cells));
+                state
+            };
+            let got = sorted_state(tiled_universe.clone().into_storage::<CellSet>());
+            let exp = sorted_state(universe.clone());
+
+            assert_eq!(got.len(), exp.len());
+            for (got, exp) in got.iter().zip(exp.iter()) {
+                assert_eq!(got.living_cells, exp.living_cells);
+                assert!((got.amplitude - exp.amplitude).norm() < 1e-12);
+            }
+            for (coordinates, probability) in universe.combined_state.iter() {
+                assert!((tiled_universe.combined_state[coordinates] - probability).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_step_configurations_order() {
+        let mut universe =
+            types::Universe::new_from_files("fixtures/state_grid_20.json", None).unwrap();
+
+        // The state and the combined state computed by a step, with or without
+        // the parallel feature, are exactly the ones obtained by stepping
+        // the configurations one after the other
+        for _ in 0..15 {
+            let rules = *universe.current_rules().unwrap();
+            let mut exp = universe.clone();
+            exp.state = Vec::new();
+            exp.combined_state = HashMap::new();
+            for configuration in universe.state.iter() {
+                exp.state.append(&mut configuration.step(
+                    rules,
+                    universe.is_even_step,
+                    universe.topology,
+                    &mut exp.combined_state,
+                ));
+            }
+            if exp.state.len() > universe.state.len() {
+                exp.solve_interference().unwrap();
+            }
+
+            universe.step().unwrap();
+            assert_eq!(universe.state, exp.state);
+            assert_eq!(universe.combined_state, exp.combined_state);
+        }
+    }
+
+    #[test]
+    fn test_step_topology() {
+        let s = step::index_to_square_state;
+        // A living cell at the top left of a square moves to its bottom right,
+        // so a single cell moves diagonally by one cell on each step
+        let diagonal = RulesBuilder::new()
+            .permutation(&[(s(8), s(1)), (s(1), s(8))])
+            .build()
+            .unwrap();
+        let content = r#"[{"amplitude": {"re": 1, "im": 0}, "living_cells": [[0, 0]]}]"#;
+
+        struct Test {
+            topology: Topology,
+            exp: Vec<Vec<(i32, i32)>>,
+        }
+
+        let tests = [
+            Test {
+                topology: Topology::Plane,
+                exp: vec![vec![(1, 1)], vec![(2, 2)], vec![(3, 3)], vec![(4, 4)]],
+            },
+            Test {
+                topology: Topology::Torus {
+                    width: 4,
+                    height: 4,
+                },
+                exp: vec![vec![(1, 1)], vec![(2, 2)], vec![(3, 3)], vec![(0, 0)]],
+            },
+            Test {
+                topology: Topology::Rectangle {
+                    width: 4,
+                    height: 4,
+                    boundary: Boundary::Absorbing,
+                },
+                exp: vec![vec![(1, 1)], vec![(2, 2)], vec![(3, 3)], vec![]],
+            },
+            Test {
+                topology: Topology::Rectangle {
+                    width: 4,
+                    height: 4,
+                    boundary: Boundary::Reflecting,
+                },
+                exp: vec![vec![(1, 1)], vec![(2, 2)], vec![(3, 3)], vec![(3, 3)]],
+            },
+        ];
+
+        for t in tests {
+            let mut universe = types::Universe::new_from_str(content)
+                .unwrap()
+                .with_topology(t.topology)
+                .unwrap();
+            universe.rules = vec![diagonal];
+
+            for exp in t.exp {
+                universe.step().unwrap();
+                assert_eq!(universe.state.len(), 1);
+                let got: Vec<(i32, i32)> = universe.state[0]
+                    .living_cells
+                    .iter()
+                    .map(|coordinates| (coordinates.x, coordinates.y))
+                    .collect();
+                assert_eq!(got, exp, "topology {:?}", t.topology);
+                assert_eq!(universe.combined_state.len(), exp.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_step_absorbed_interference() {
+        let s = step::index_to_square_state;
+        let diagonal = RulesBuilder::new()
+            .permutation(&[(s(8), s(1)), (s(1), s(8))])
+            .build()
+            .unwrap();
+        let mut universe = types::Universe::new_from_str(
+            r#"[
+                {"amplitude": {"re": 0.6, "im": 0}, "living_cells": [[0, 0]]},
+                {"amplitude": {"re": 0.8, "im": 0}, "living_cells": []}
+            ]"#,
+        )
+        .unwrap()
+        .with_topology(Topology::Rectangle {
+            width: 2,
+            height: 2,
+            boundary: Boundary::Absorbing,
+        })
+        .unwrap();
+        universe.rules = vec![diagonal];
+
+        // The cell moves to (1, 1), then out of the rectangle without any split,
+        // and the configuration left empty interferes with the other one
+        let report = universe.step().unwrap();
+        assert_eq!((report.splits, report.merges), (0, 0));
+        assert_eq!(universe.state.len(), 2);
+
+        let report = universe.step().unwrap();
+        assert_eq!((report.splits, report.merges), (0, 1));
+        assert_eq!(universe.state.len(), 1);
+        assert!(universe.state[0].living_cells.is_empty());
+        assert!((universe.state[0].amplitude - Complex::new(1.4, 0.)).norm() < 1e-12);
+        assert!((report.norm_after - 1.96).abs() < 1e-12);
+        assert!((report.drift - 0.96).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_step_pruning_without_split() {
+        let s = step::index_to_square_state;
+        let diagonal = RulesBuilder::new()
+            .permutation(&[(s(8), s(1)), (s(1), s(8))])
+            .build()
+            .unwrap();
+        let mut universe = types::Universe::new_from_str(
+            r#"[
+                {"amplitude": {"re": 0.6, "im": 0}, "living_cells": [[0, 0]]},
+                {"amplitude": {"re": 0.8, "im": 0}, "living_cells": [[10, 10]]}
+            ]"#,
+        )
+        .unwrap()
+        .with_pruning(PruningPolicy {
+            strategy: PruningStrategy::TopK { k: 1 },
+            renormalize: true,
+        })
+        .unwrap();
+        universe.rules = vec![diagonal];
+
+        let report = universe.step().unwrap();
+        assert_eq!(report.splits, 0);
+        assert!(universe.state.len() <= 1);
+        assert_eq!(report.pruned.configurations, 1);
+        assert!((report.pruned.probability - 0.36).abs() < 1e-12);
+        assert_eq!(
+            universe.state[0].living_cells.iter().collect::<Vec<_>>(),
+            vec![&types::Coordinates { x: 11, y: 11 }]
+        );
+        assert!((universe.state[0].amplitude - Complex::new(1., 0.)).norm() < 1e-12);
+        assert!(report.drift.abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let fixtures = [
+            "fixtures/state_2_diagonal_cells.json",
+            "fixtures/state_2_adjacent_cells.json",
+            "fixtures/state_grid_20.json",
+        ];
+
+        for fixture in fixtures {
+            let exp = types::Universe::new_from_files(fixture, None).unwrap();
+            let mut universe = exp.clone();
+
+            for _ in 0..6 {
+                universe.step().unwrap();
+            }
+            for _ in 0..6 {
+                universe.step_back().unwrap();
+            }
+
+            assert_eq!(universe.step_count, 0);
+            assert!(universe.is_even_step);
+            assert_eq!(universe.state.len(), 1);
+            assert_eq!(universe.state[0].living_cells, exp.state[0].living_cells);
+            assert!((universe.state[0].amplitude - exp.state[0].amplitude).norm() < 1e-9);
+
+            // Stepping back from the first step does nothing
+            universe.step_back().unwrap();
+            assert_eq!(universe.step_count, 0);
+            assert!(universe.is_even_step);
+        }
+    }
+
+    #[test]
+    fn test_step_report() {
+        let mut universe =
+            types::Universe::new_from_files("fixtures/state_2_diagonal_cells.json", None)
+                .unwrap()
+                .with_strict_mode(1e-9);
+
+        // Every configuration is added by a split and removed by a merge or a pruning
+        let mut configurations = universe.state.len();
+        let (mut splits, mut merges) = (0, 0);
+        for _ in 0..16 {
+            let report = universe.step().unwrap();
+            splits += report.splits;
+            merges += report.merges;
+            configurations += report.splits;
+            configurations -= report.merges + report.pruned.configurations;
+            assert_eq!(universe.state.len(), configurations);
+            assert_eq!(report.norm_after, universe.total_probability());
+            assert!((report.norm_before - 1.).abs() < 1e-9);
+        }
+        assert!(splits > 0 && merges > 0);
+
+        // Rules that double the amplitudes are not unitary
+        let mut double = rules::get_default_rules();
+        for (i, row) in double.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                *entry = Complex::new(if i == j { 2. } else { 0. }, 0.);
+            }
+        }
+        universe.rules = vec![double];
+        assert!(matches!(
+            universe.step(),
+            Err(Error::ProbabilityDrift { step: 17, .. })
+        ));
+        assert!(!universe.is_even_step);
+    }
 
     #[test]
     fn test_compute_rule() {
-        let rules = types::get_test_rules();
+        let rules = rules::get_default_rules();
 
         struct Test {
             rules: types::Rules,
@@ -0,0 +1,267 @@
+use serde::de::Deserializer;
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use super::cell_set::CellSet;
+use super::living_cells::{self, LivingCells};
+use super::types::Coordinates;
+
+// Number of cells on each side of a tile
+const TILE_SIZE: i32 = 8;
+
+// TiledCells stores the living cells in 8 * 8 tiles,
+// the cells of a tile being the bits of a u64 bitmask
+//
+// The tile (tx, ty) contains the cells with tx * 8 <= x < (tx + 1) * 8
+// and ty * 8 <= y < (ty + 1) * 8, the cell (x, y) being the bit
+// (x - tx * 8) * 8 + (y - ty * 8) of the bitmask
+//
+// Since the tiles are aligned on even coordinates, a 2 * 2 square
+// of an even step is always inside a single tile and its state is read
+// with a single lookup, as for most of the squares of an odd step
+//
+// The tiles are stored in a vector sorted by tile coordinates and only the tiles
+// containing at least one living cell are stored, so that two TiledCells
+// with the same living cells are equal
+//
+// TiledCells are serialized as a CellSet
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TiledCells {
+    tiles: Vec<((i32, i32), u64)>,
+}
+
+impl TiledCells {
+    fn mask(&self, tile: &(i32, i32)) -> u64 {
+        match self
+            .tiles
+            .binary_search_by_key(tile, |(position, _)| *position)
+        {
+            Ok(index) => self.tiles[index].1,
+            Err(_) => 0,
+        }
+    }
+}
+
+// tile_position returns the coordinates of the tile containing the cell
+// and the index of the bit of the cell in the tile
+fn tile_position(coordinates: &Coordinates) -> ((i32, i32), u32) {
+    let tile = (
+        coordinates.x.div_euclid(TILE_SIZE),
+        coordinates.y.div_euclid(TILE_SIZE),
+    );
+    let bit = coordinates.x.rem_euclid(TILE_SIZE) * TILE_SIZE + coordinates.y.rem_euclid(TILE_SIZE);
+    (tile, bit as u32)
+}
+
+impl LivingCells for TiledCells {
+    fn from_unsorted(cells: Vec<Coordinates>) -> Self {
+        let mut positions: Vec<((i32, i32), u32)> = cells.iter().map(tile_position).collect();
+        positions.sort_unstable();
+
+        let mut tiles: Vec<((i32, i32), u64)> = Vec::new();
+        for (tile, bit) in positions {
+            match tiles.last_mut() {
+                Some((last_tile, mask)) if *last_tile == tile => *mask |= 1 << bit,
+                _ => tiles.push((tile, 1 << bit)),
+            }
+        }
+        Self { tiles }
+    }
+
+    fn len(&self) -> usize {
+        self.tiles
+            .iter()
+            .map(|(_, mask)| mask.count_ones() as usize)
+            .sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    fn contains(&self, coordinates: &Coordinates) -> bool {
+        let (tile, bit) = tile_position(coordinates);
+        self.mask(&tile) & (1 << bit) != 0
+    }
+
+    fn insert(&mut self, coordinates: Coordinates) -> bool {
+        let (tile, bit) = tile_position(&coordinates);
+        match self
+            .tiles
+            .binary_search_by_key(&tile, |(position, _)| *position)
+        {
+            Ok(index) => {
+                let mask = &mut self.tiles[index].1;
+                let inserted = *mask & (1 << bit) == 0;
+                *mask |= 1 << bit;
+                inserted
+            }
+            Err(index) => {
+                self.tiles.insert(index, (tile, 1 << bit));
+                true
+            }
+        }
+    }
+
+    fn remove(&mut self, coordinates: &Coordinates) -> bool {
+        let (tile, bit) = tile_position(coordinates);
+        match self
+            .tiles
+            .binary_search_by_key(&tile, |(position, _)| *position)
+        {
+            Ok(index) if self.tiles[index].1 & (1 << bit) != 0 => {
+                self.tiles[index].1 &= !(1 << bit);
+                if self.tiles[index].1 == 0 {
+                    self.tiles.remove(index);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = Coordinates> + '_> {
+        // The set bits of each mask are visited from the lowest one,
+        // which is cleared once its cell is returned
+        Box::new(self.tiles.iter().flat_map(|((tx, ty), mask)| {
+            let mut mask = *mask;
+            std::iter::from_fn(move || {
+                if mask == 0 {
+                    return None;
+                }
+                let bit = mask.trailing_zeros() as i32;
+                mask &= mask - 1;
+                Some(Coordinates {
+                    x: tx * TILE_SIZE + bit / TILE_SIZE,
+                    y: ty * TILE_SIZE + bit % TILE_SIZE,
+                })
+            })
+        }))
+    }
+
+    fn square_state(&self, x_min: i32, y_min: i32) -> [bool; 4] {
+        let (tile, bit) = tile_position(&Coordinates { x: x_min, y: y_min });
+
+        // The square overlaps several tiles if it is on the last row or column of a tile
+        if x_min.rem_euclid(TILE_SIZE) == TILE_SIZE - 1
+            || y_min.rem_euclid(TILE_SIZE) == TILE_SIZE - 1
+        {
+            return [
+                self.contains(&Coordinates { x: x_min, y: y_min }),
+                self.contains(&Coordinates {
+                    x: x_min,
+                    y: y_min + 1,
+                }),
+                self.contains(&Coordinates {
+                    x: x_min + 1,
+                    y: y_min,
+                }),
+                self.contains(&Coordinates {
+                    x: x_min + 1,
+                    y: y_min + 1,
+                }),
+            ];
+        }
+
+        let mask = self.mask(&tile);
+        let next_row_bit = bit + TILE_SIZE as u32;
+        [
+            mask & (1 << bit) != 0,
+            mask & (1 << (bit + 1)) != 0,
+            mask & (1 << next_row_bit) != 0,
+            mask & (1 << (next_row_bit + 1)) != 0,
+        ]
+    }
+
+    // The hash is the sum of the hashes of the tiles and their bitmask
+    fn interference_hash(&self) -> u64 {
+        self.tiles.iter().fold(0, |hash, ((tx, ty), mask)| {
+            let tile_hash = living_cells::mix_hash(living_cells::pack_pair(*tx, *ty));
+            hash.wrapping_add(living_cells::mix_hash(tile_hash ^ mask))
+        })
+    }
+}
+
+impl Serialize for TiledCells {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CellSet::from_unsorted(self.cells().collect()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TiledCells {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let cell_set = CellSet::deserialize(deserializer)?;
+        Ok(Self::from_unsorted(cell_set.iter().cloned().collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::cell_set::CellSet;
+    use crate::universe::living_cells::LivingCells;
+    use crate::universe::tiled_cells::TiledCells;
+    use crate::universe::types::Coordinates;
+
+    #[test]
+    fn test_tiled_cells() {
+        let c = |x, y| Coordinates { x, y };
+        let cells = vec![c(0, 0), c(7, 7), c(8, 7), c(-1, -1), c(-8, 3), c(15, -9)];
+        let mut tiled_cells = TiledCells::from_unsorted(cells.clone());
+        let cell_set = CellSet::from_unsorted(cells.clone());
+
+        assert_eq!(tiled_cells.len(), cells.len());
+        for cell in cells.iter() {
+            assert!(tiled_cells.contains(cell));
+        }
+        assert!(!tiled_cells.contains(&c(1, 0)));
+        assert!(!tiled_cells.contains(&c(-1, 0)));
+        assert_eq!(
+            CellSet::from_unsorted(tiled_cells.cells().collect()),
+            cell_set
+        );
+
+        assert!(!tiled_cells.insert(c(8, 7)));
+        assert!(tiled_cells.remove(&c(8, 7)));
+        assert!(!tiled_cells.remove(&c(8, 7)));
+        assert!(tiled_cells.insert(c(8, 7)));
+        assert_eq!(tiled_cells, TiledCells::from_unsorted(cells));
+    }
+
+    #[test]
+    fn test_tiled_cells_square_state() {
+        let c = |x, y| Coordinates { x, y };
+        let cells = vec![
+            c(0, 0),
+            c(1, 1),
+            c(6, 7),
+            c(7, 7),
+            c(8, 8),
+            c(-1, 0),
+            c(0, -1),
+        ];
+        let tiled_cells = TiledCells::from_unsorted(cells.clone());
+        let cell_set = CellSet::from_unsorted(cells);
+
+        // Squares inside a tile and overlapping several tiles
+        for x_min in -3..10 {
+            for y_min in -3..10 {
+                assert_eq!(
+                    tiled_cells.square_state(x_min, y_min),
+                    cell_set.square_state(x_min, y_min),
+                    "square ({x_min}, {y_min})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tiled_cells_serde() {
+        let content = "[[9, -2], [0, 1]]";
+        let tiled_cells: TiledCells = serde_json::from_str(content).unwrap();
+        assert!(tiled_cells.contains(&Coordinates { x: 9, y: -2 }));
+        assert_eq!(
+            serde_json::to_string(&tiled_cells).unwrap(),
+            "[[0,1],[9,-2]]"
+        );
+    }
+}
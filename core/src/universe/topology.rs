@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+
+use super::living_cells::LivingCells;
+use super::types::{Coordinates, Universe};
+use crate::Error;
+
+// The topology of the universe defines where cells can live
+//
+// - Plane: the cells live on an infinite plane (the coordinates are only bounded by i32)
+// - Rectangle: the cells live in the rectangle 0 <= x < width, 0 <= y < height,
+//   the 2 * 2 squares crossing the border of the rectangle follow its boundary
+// - Torus: the cells live in the rectangle 0 <= x < width, 0 <= y < height
+//   whose opposite borders are joined, the 2 * 2 squares crossing a border wrap around,
+//   the width and the height must be even for the squares of each step to cover the torus
+//
+// Example (JSON):
+// {"type": "rectangle", "width": 20, "height": 10, "boundary": "reflecting"}
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Topology {
+    #[default]
+    Plane,
+    Rectangle {
+        width: i32,
+        height: i32,
+        boundary: Boundary,
+    },
+    Torus {
+        width: i32,
+        height: i32,
+    },
+}
+
+// The boundary defines how the rules apply on the 2 * 2 squares
+// crossing the border of a rectangle
+//
+// - Absorbing: the rules apply as if the cells outside the rectangle were dead,
+//   and the cells that the rules bring outside the rectangle disappear
+//   (some probability can then be lost by interference and the evolution is not reversible)
+// - Reflecting: the rules don't apply, the cells of the square stay where they are
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    Absorbing,
+    Reflecting,
+}
+
+impl Topology {
+    pub fn validate(&self) -> Result<(), Error> {
+        match *self {
+            Topology::Plane => Ok(()),
+            Topology::Rectangle { width, height, .. } if width <= 0 || height <= 0 => {
+                Err(Error::InvalidTopology(format!(
+                    "the size of a rectangle must be positive, got {width}x{height}"
+                )))
+            }
+            Topology::Rectangle { .. } => Ok(()),
+            Topology::Torus { width, height }
+                if width <= 0 || height <= 0 || width % 2 != 0 || height % 2 != 0 =>
+            {
+                Err(Error::InvalidTopology(format!(
+                    "the size of a torus must be positive and even, got {width}x{height}"
+                )))
+            }
+            Topology::Torus { .. } => Ok(()),
+        }
+    }
+
+    // contains returns whether a cell at the given coordinates can live in the universe
+    pub fn contains(&self, coordinates: &Coordinates) -> bool {
+        match *self {
+            Topology::Plane => true,
+            Topology::Rectangle { width, height, .. } | Topology::Torus { width, height } => {
+                (0..width).contains(&coordinates.x) && (0..height).contains(&coordinates.y)
+            }
+        }
+    }
+
+    // wrap returns the coordinates wrapped around the torus,
+    // the coordinates are unchanged with another topology
+    pub fn wrap(&self, coordinates: Coordinates) -> Coordinates {
+        match *self {
+            Topology::Torus { width, height } => Coordinates {
+                x: coordinates.x.rem_euclid(width),
+                y: coordinates.y.rem_euclid(height),
+            },
+            _ => coordinates,
+        }
+    }
+
+    // square_cells returns the 4 cells of the 2 * 2 square whose top left cell is (x_min, y_min)
+    // in the order of a square state, each cell being None if it is outside the universe
+    pub(crate) fn square_cells(&self, x_min: i32, y_min: i32) -> [Option<Coordinates>; 4] {
+        [(0, 0), (0, 1), (1, 0), (1, 1)].map(|(dx, dy)| {
+            let coordinates = self.wrap(Coordinates {
+                x: x_min + dx,
+                y: y_min + dy,
+            });
+            if self.contains(&coordinates) {
+                Some(coordinates)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn boundary(&self) -> Option<Boundary> {
+        match *self {
+            Topology::Rectangle { boundary, .. } => Some(boundary),
+            _ => None,
+        }
+    }
+}
+
+impl<C: LivingCells> Universe<C> {
+    // with_topology sets the topology of the universe
+    //
+    // The living cells are wrapped around a torus, and an error is returned
+    // if a living cell is outside a rectangle or if two living cells of a configuration
+    // are wrapped on the same cell of a torus
+    //
+    // The configurations that have the same living cells once wrapped interfere
+    // (see `Universe::solve_interference`)
+    pub fn with_topology(mut self, topology: Topology) -> Result<Self, Error> {
+        topology.validate()?;
+
+        let mut wrapped = false;
+        for configuration in self.state.iter_mut() {
+            if let Some(coordinates) = configuration
+                .living_cells
+                .cells()
+                .find(|coordinates| !topology.contains(&topology.wrap(coordinates.clone())))
+            {
+                return Err(Error::InvalidTopology(format!(
+                    "the living cell {coordinates:?} is outside the universe"
+                )));
+            }
+
+            if matches!(topology, Topology::Torus { .. }) {
+                let living_cells = C::from_unsorted(
+                    configuration
+                        .living_cells
+                        .cells()
+                        .map(|coordinates| topology.wrap(coordinates))
+                        .collect(),
+                );
+                if living_cells.len() != configuration.living_cells.len() {
+                    return Err(Error::InvalidTopology(
+                        "two living cells of a configuration are wrapped on the same cell"
+                            .to_string(),
+                    ));
+                }
+                wrapped |= living_cells != configuration.living_cells;
+                configuration.living_cells = living_cells;
+            }
+        }
+
+        self.topology = topology;
+        self.compute_combined_state();
+        if wrapped {
+            self.solve_interference()?;
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::topology::{Boundary, Topology};
+    use crate::universe::types::{Coordinates, Universe};
+    use crate::Error;
+    use num::complex::Complex;
+
+    #[test]
+    fn test_square_cells() {
+        let c = |x, y| Some(Coordinates { x, y });
+
+        struct Test {
+            topology: Topology,
+            square: (i32, i32),
+            exp: [Option<Coordinates>; 4],
+        }
+
+        let tests = [
+            Test {
+                topology: Topology::Plane,
+                square: (-1, -1),
+                exp: [c(-1, -1), c(-1, 0), c(0, -1), c(0, 0)],
+            },
+            Test {
+                topology: Topology::Rectangle {
+                    width: 4,
+                    height: 6,
+                    boundary: Boundary::Absorbing,
+                },
+                square: (3, -1),
+                exp: [None, c(3, 0), None, None],
+            },
+            Test {
+                topology: Topology::Torus {
+                    width: 4,
+                    height: 6,
+                },
+                square: (3, 5),
+                exp: [c(3, 5), c(3, 0), c(0, 5), c(0, 0)],
+            },
+        ];
+
+        for t in tests {
+            assert_eq!(t.topology.square_cells(t.square.0, t.square.1), t.exp);
+        }
+    }
+
+    #[test]
+    fn test_with_topology() {
+        let content = r#"[{"amplitude": {"re": 1, "im": 0}, "living_cells": [[0, 0], [5, 1]]}]"#;
+
+        let universe = Universe::new_from_str(content).unwrap();
+        let torus = Topology::Torus {
+            width: 4,
+            height: 4,
+        };
+        let universe = universe.with_topology(torus).unwrap();
+        assert!(universe.state[0]
+            .living_cells
+            .contains(&Coordinates { x: 1, y: 1 }));
+        assert!(universe
+            .combined_state
+            .contains_key(&Coordinates { x: 1, y: 1 }));
+
+        let universe = Universe::new_from_str(content).unwrap();
+        let rectangle = Topology::Rectangle {
+            width: 4,
+            height: 4,
+            boundary: Boundary::Reflecting,
+        };
+        assert!(matches!(
+            universe.with_topology(rectangle),
+            Err(Error::InvalidTopology(_))
+        ));
+
+        // The cells (0, 0) and (4, 0) are the same cell of the torus
+        let universe = Universe::new_from_str(
+            r#"[{"amplitude": {"re": 1, "im": 0}, "living_cells": [[0, 0], [4, 0]]}]"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            universe.with_topology(torus),
+            Err(Error::InvalidTopology(_))
+        ));
+
+        // The configurations are the same once wrapped, so they interfere
+        let universe = Universe::new_from_str(
+            r#"[
+                {"amplitude": {"re": 0.6, "im": 0}, "living_cells": [[0, 0]]},
+                {"amplitude": {"re": 0.6, "im": 0}, "living_cells": [[4, 0]]},
+                {"amplitude": {"re": 0, "im": 0.5291502622129182}, "living_cells": [[1, 0]]}
+            ]"#,
+        )
+        .unwrap();
+        let universe = universe.with_topology(torus).unwrap();
+        assert_eq!(universe.state.len(), 2);
+        assert_eq!(universe.state[0].amplitude, Complex::new(1.2, 0.));
+        assert!((universe.combined_state[&Coordinates { x: 0, y: 0 }] - 1.44).abs() < 1e-12);
+
+        let universe = Universe::new_from_str(content).unwrap();
+        let odd_torus = Topology::Torus {
+            width: 6,
+            height: 3,
+        };
+        assert!(matches!(
+            universe.with_topology(odd_torus),
+            Err(Error::InvalidTopology(_))
+        ));
+    }
+}
@@ -0,0 +1,299 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use super::living_cells::LivingCells;
+use super::observables;
+use super::types::*;
+use crate::Error;
+
+// Monte Carlo quantum trajectories: the universe is evolved several times independently,
+// each evolution (trajectory) being measured randomly during the steps, and the occupations
+// of the cells at the end of the trajectories are averaged
+//
+// The measures keep the number of configurations of each trajectory small,
+// which makes it possible to study rules whose configurations split too much
+// to compute the whole state
+//
+// Example: 100 trajectories of 50 steps, measuring the whole universe
+// after each step with a probability of 0.1
+// TrajectoryOptions {
+//     trajectories: 100,
+//     steps: 50,
+//     measurement: TrajectoryMeasurement::Global,
+//     rate: 0.1,
+//     seed: 0,
+// }
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrajectoryOptions {
+    pub trajectories: usize,
+    pub steps: usize,
+    pub measurement: TrajectoryMeasurement,
+    // Probability of each measure after each step, between 0 and 1
+    pub rate: f64,
+    // The trajectory i uses the stream i of the random number generator seeded with the seed,
+    // so the results only depend on the seed, with or without the parallel feature
+    pub seed: u64,
+}
+
+// - Global: measure the whole universe (see `Universe::measure`)
+// - Regions: measure each region independently (see `Universe::measure_region`)
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrajectoryMeasurement {
+    Global,
+    Regions(Vec<Vec<Coordinates>>),
+}
+
+// Statistics of the occupations of the cells at the end of the trajectories
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TrajectoryStatistics {
+    pub trajectories: usize,
+    pub steps: usize,
+    // Total number of measures of all the trajectories
+    pub measures: usize,
+    // Cells alive in at least one trajectory, sorted by coordinates
+    pub cells: Vec<CellStatistics>,
+}
+
+// The occupation of a cell in a trajectory is its probability to be alive
+// at the end of the trajectory (see `observables::occupation`)
+//
+// The confidence interval is the 95% confidence interval of the mean occupation,
+// mean ± 1.96 * standard_deviation / sqrt(trajectories) (normal approximation)
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CellStatistics {
+    pub cell: Coordinates,
+    pub mean: f64,
+    pub standard_deviation: f64,
+    pub confidence_interval: (f64, f64),
+}
+
+// z-score of the 95% confidence intervals
+const CONFIDENCE_Z_SCORE: f64 = 1.96;
+
+impl TrajectoryOptions {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.trajectories == 0 {
+            return Err(Error::InvalidTrajectoryOptions(
+                "the number of trajectories is 0".to_string(),
+            ));
+        }
+        if !(0. ..=1.).contains(&self.rate) {
+            return Err(Error::InvalidTrajectoryOptions(format!(
+                "the rate {} is not between 0 and 1",
+                self.rate
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<C: LivingCells> Universe<C> {
+    // run_trajectories evolves copies of the universe along independent trajectories
+    // and returns the statistics of the occupations of the cells at their end
+    // (see `TrajectoryOptions`), the universe itself is not changed
+    //
+    // The trajectories are computed on several threads with the parallel feature
+    pub fn run_trajectories(
+        &self,
+        options: &TrajectoryOptions,
+    ) -> Result<TrajectoryStatistics, Error> {
+        options.validate()?;
+
+        #[cfg(feature = "parallel")]
+        let results: Vec<(HashMap<Coordinates, f64>, usize)> = (0..options.trajectories)
+            .into_par_iter()
+            .map(|i| self.run_trajectory(options, i))
+            .collect::<Result<_, Error>>()?;
+        #[cfg(not(feature = "parallel"))]
+        let results: Vec<(HashMap<Coordinates, f64>, usize)> = (0..options.trajectories)
+            .map(|i| self.run_trajectory(options, i))
+            .collect::<Result<_, Error>>()?;
+
+        // The sums are computed in the order of the trajectories for the result
+        // not to depend on the threads
+        let mut sums: BTreeMap<Coordinates, (f64, f64)> = BTreeMap::new();
+        let mut measures = 0;
+        for (occupations, trajectory_measures) in results {
+            measures += trajectory_measures;
+            for (cell, occupation) in occupations {
+                let (sum, sum_of_squares) = sums.entry(cell).or_insert((0., 0.));
+                *sum += occupation;
+                *sum_of_squares += occupation * occupation;
+            }
+        }
+
+        // The cells dead in a trajectory have an occupation of 0 in it
+        let n = options.trajectories as f64;
+        let cells = sums
+            .into_iter()
+            .map(|(cell, (sum, sum_of_squares))| {
+                let mean = sum / n;
+                let variance = if options.trajectories > 1 {
+                    ((sum_of_squares - n * mean * mean) / (n - 1.)).max(0.)
+                } else {
+                    0.
+                };
+                let standard_deviation = variance.sqrt();
+                let margin = CONFIDENCE_Z_SCORE * standard_deviation / n.sqrt();
+                CellStatistics {
+                    cell,
+                    mean,
+                    standard_deviation,
+                    confidence_interval: (mean - margin, mean + margin),
+                }
+            })
+            .collect();
+
+        Ok(TrajectoryStatistics {
+            trajectories: options.trajectories,
+            steps: options.steps,
+            measures,
+            cells,
+        })
+    }
+
+    // run_trajectory computes the trajectory i and returns the occupations
+    // of the cells at its end and the number of measures
+    fn run_trajectory(
+        &self,
+        options: &TrajectoryOptions,
+        i: usize,
+    ) -> Result<(HashMap<Coordinates, f64>, usize), Error> {
+        let mut trajectory = self.clone();
+        trajectory.history = None;
+        trajectory.rng = ChaCha8Rng::seed_from_u64(options.seed);
+        trajectory.rng.set_stream(i as u64);
+
+        let mut measures = 0;
+        for _ in 0..options.steps {
+            trajectory.step()?;
+            match &options.measurement {
+                TrajectoryMeasurement::Global => {
+                    if trajectory.rng.gen_bool(options.rate) {
+                        trajectory.measure()?;
+                        measures += 1;
+                    }
+                }
+                TrajectoryMeasurement::Regions(regions) => {
+                    for region in regions.iter() {
+                        if trajectory.rng.gen_bool(options.rate) {
+                            trajectory.measure_region(region)?;
+                            measures += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok((observables::occupations(&trajectory.state), measures))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::universe::trajectories::{TrajectoryMeasurement, TrajectoryOptions};
+    use crate::universe::types::{Coordinates, Universe};
+    use crate::Error;
+
+    #[test]
+    fn test_run_trajectories() {
+        let universe =
+            Universe::new_from_files("fixtures/state_2_diagonal_cells.json", None).unwrap();
+        let c = |x, y| Coordinates { x, y };
+
+        struct Test {
+            measurement: TrajectoryMeasurement,
+            rate: f64,
+            // None if the number of measures is random
+            exp_measures: Option<usize>,
+        }
+
+        let tests = [
+            Test {
+                measurement: TrajectoryMeasurement::Global,
+                rate: 0.,
+                exp_measures: Some(0),
+            },
+            Test {
+                measurement: TrajectoryMeasurement::Global,
+                rate: 1.,
+                exp_measures: Some(60),
+            },
+            Test {
+                measurement: TrajectoryMeasurement::Regions(vec![vec![c(10, 10)], vec![c(11, 11)]]),
+                rate: 0.5,
+                exp_measures: None,
+            },
+        ];
+
+        for t in tests {
+            let options = TrajectoryOptions {
+                trajectories: 20,
+                steps: 3,
+                measurement: t.measurement.clone(),
+                rate: t.rate,
+                seed: 3,
+            };
+            let statistics = universe.run_trajectories(&options).unwrap();
+            assert_eq!(statistics, universe.run_trajectories(&options).unwrap());
+
+            let mut sorted_cells: Vec<Coordinates> = statistics
+                .cells
+                .iter()
+                .map(|statistics| statistics.cell.clone())
+                .collect();
+            sorted_cells.sort();
+            assert_eq!(
+                sorted_cells,
+                statistics
+                    .cells
+                    .iter()
+                    .map(|statistics| statistics.cell.clone())
+                    .collect::<Vec<_>>()
+            );
+
+            // The number of living cells is kept by the default rules,
+            // so the mean occupations sum to 2
+            let total: f64 = statistics.cells.iter().map(|cell| cell.mean).sum();
+            assert!((total - 2.).abs() < 1e-9, "{:?}", t.measurement);
+            for cell in statistics.cells.iter() {
+                assert!(cell.confidence_interval.0 <= cell.mean);
+                assert!(cell.mean <= cell.confidence_interval.1);
+            }
+
+            match t.exp_measures {
+                Some(exp_measures) => assert_eq!(statistics.measures, exp_measures),
+                None => assert!(statistics.measures > 0 && statistics.measures < 120),
+            }
+
+            // Without measures, every trajectory is the exact evolution,
+            // whose occupations are the combined state
+            if t.rate == 0. {
+                let mut exact = universe.clone();
+                for _ in 0..3 {
+                    exact.step().unwrap();
+                }
+                for cell in statistics.cells.iter() {
+                    assert_eq!(cell.standard_deviation, 0.);
+                    assert!((cell.mean - exact.combined_state[&cell.cell]).abs() < 1e-9);
+                }
+            }
+        }
+
+        let options = TrajectoryOptions {
+            trajectories: 1,
+            steps: 1,
+            measurement: TrajectoryMeasurement::Global,
+            rate: 1.5,
+            seed: 0,
+        };
+        assert!(matches!(
+            universe.run_trajectories(&options),
+            Err(Error::InvalidTrajectoryOptions(_))
+        ));
+    }
+}
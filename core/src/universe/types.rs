@@ -1,10 +1,16 @@
-use super::files;
+use super::cell_set::CellSet;
+use super::history::History;
+use super::living_cells::LivingCells;
+use super::pruning::PruningPolicy;
+use super::rules::RulesError;
+use super::topology::Topology;
+use super::{files, rules};
+use crate::{AmplitudesError, Error};
 use num::complex::Complex;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
 use std::collections::HashMap;
-use std::f64::consts::PI;
-use std::io::Error;
 
 #[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub struct Coordinates {
@@ -21,21 +27,17 @@ pub struct ComplexDef<T> {
     pub im: T,
 }
 
-// The HashMap bool value in the living_cells attribute
-// is true if this cell has already been computed during the current step
-// We decided to do that to optimize memory but it needs to be reviewed later
-// use a struct would be better for readability
-#[serde_as]
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Configuration {
+// The living cells are stored in a CellSet unless another storage
+// is given as type parameter (see `living_cells::LivingCells`)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Configuration<C = CellSet> {
     #[serde(with = "ComplexDef")]
     pub amplitude: Complex<f64>,
 
-    #[serde_as(as = "Vec<(_, _)>")]
-    pub living_cells: HashMap<Coordinates, bool>,
+    pub living_cells: C,
 }
 
-pub type State = Vec<Configuration>;
+pub type State<C = CellSet> = Vec<Configuration<C>>;
 
 // The Rules defines a 16x16 grid of complex number
 pub type Rules = [[Complex<f64>; 16]; 16];
@@ -43,13 +45,38 @@ pub type Rules = [[Complex<f64>; 16]; 16];
 // The is_even_step attribute is used to determine the square in which
 // the rules of the universe apply for a given living cell
 // It is true if the universe is in an even step and false othrerwise
+//
+// The rules attribute is a cycle of rules, the rules applied on a step
+// are rules[step_count % rules.len()] (see `current_rules`)
+// e.g. with 2 rules, the first one applies on even steps and the second one on odd steps
+//
+// The topology attribute defines where the cells can live (see `topology::Topology`),
+// the universe is an infinite plane unless a topology is given with `with_topology`
+//
+// The pruning attribute defines the configurations discarded after the interferences
+// of each step (see `pruning::PruningPolicy`), it can be changed with `with_pruning`
+//
+// The probability_tolerance attribute enables the strict mode: if it is set, a step returns
+// an error when the total probability drifts by more than the tolerance (see `with_strict_mode`)
+//
+// The history attribute records the branching of the configurations
+// once enabled with `with_history` (see `history::History`)
+//
+// The rng attribute is the random number generator used by the measures,
+// it is seeded from the system entropy unless a seed is given with `with_seed`
+// and can be serialized so that a saved universe resumes with the same measures
 #[derive(Clone, Debug)]
-pub struct Universe {
-    pub state: State,
+pub struct Universe<C = CellSet> {
+    pub state: State<C>,
     pub combined_state: HashMap<Coordinates, f64>,
     pub is_even_step: bool,
-    pub rules: Rules,
+    pub rules: Vec<Rules>,
     pub step_count: usize,
+    pub topology: Topology,
+    pub pruning: PruningPolicy,
+    pub probability_tolerance: Option<f64>,
+    pub history: Option<History>,
+    pub rng: ChaCha8Rng,
 }
 
 impl Default for Universe {
@@ -58,43 +85,68 @@ impl Default for Universe {
     }
 }
 
+// Options of the constructors reading a state
+//
+// normalize: scale the amplitudes of the state so that its total probability is 1
+// (see `Universe::normalize`), a state whose total probability is not 1
+// is otherwise kept as is (see `Universe::validate_normalization`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+    pub normalize: bool,
+}
+
 impl Universe {
     pub fn new() -> Self {
         // Unique configuration with no living cell
         let configuration = Configuration {
             amplitude: Complex::new(1., 0.),
-            living_cells: HashMap::new(),
+            living_cells: CellSet::new(),
         };
-        let state = vec![configuration];
-        let rules = get_test_rules();
-        let step_count = 0;
-        Self {
-            state,
-            combined_state: HashMap::new(),
-            is_even_step: true,
-            rules,
-            step_count,
-        }
+        Self::new_from_state(vec![configuration], vec![rules::get_default_rules()])
+            .expect("the default rules should be unitary")
     }
 
-    pub fn new_from_files(state_file: &str) -> Result<Self, Error> {
+    // The rules_file is optional, the default rules are used if none is provided
+    pub fn new_from_files(state_file: &str, rules_file: Option<&str>) -> Result<Self, Error> {
+        Self::new_from_files_with(state_file, rules_file, LoadOptions::default())
+    }
+
+    pub fn new_from_files_with(
+        state_file: &str,
+        rules_file: Option<&str>,
+        options: LoadOptions,
+    ) -> Result<Self, Error> {
         let state = files::get_state_from_file(state_file)?;
-        let rules = get_test_rules();
-        let step_count = 0;
-        let mut universe = Self {
-            state,
-            combined_state: HashMap::new(),
-            is_even_step: true,
-            rules,
-            step_count,
+        let rules = match rules_file {
+            Some(rules_file) => files::get_rules_from_file(rules_file)?,
+            None => vec![rules::get_default_rules()],
         };
-        universe.compute_combined_state();
-        Ok(universe)
+        Self::new_from_state(state, rules)?.with_load_options(options)
     }
 
     pub fn new_from_str(content: &str) -> Result<Self, Error> {
+        Self::new_from_str_with(content, LoadOptions::default())
+    }
+
+    pub fn new_from_str_with(content: &str, options: LoadOptions) -> Result<Self, Error> {
         let state: State = serde_json::from_str(content)?;
-        let rules = get_test_rules();
+        Self::new_from_state(state, vec![rules::get_default_rules()])?.with_load_options(options)
+    }
+
+    pub(crate) fn with_load_options(mut self, options: LoadOptions) -> Result<Self, Error> {
+        if options.normalize {
+            self.normalize()?;
+        }
+        Ok(self)
+    }
+}
+
+impl<C: LivingCells> Universe<C> {
+    // The rules are rejected with a rules::RulesError if they are not unitary
+    // and the state with an AmplitudesError if its amplitudes are not probabilities
+    pub fn new_from_state(state: State<C>, rules: Vec<Rules>) -> Result<Self, Error> {
+        rules::validate_rules_cycle(&rules, rules::UNITARITY_TOLERANCE)?;
+        validate_amplitudes(&state)?;
         let step_count = 0;
         let mut universe = Self {
             state,
@@ -102,307 +154,90 @@ impl Universe {
             is_even_step: true,
             rules,
             step_count,
+            topology: Topology::Plane,
+            pruning: PruningPolicy::default(),
+            probability_tolerance: None,
+            history: None,
+            rng: ChaCha8Rng::from_entropy(),
         };
         universe.compute_combined_state();
         Ok(universe)
     }
+
+    // with_seed seeds the random number generator of the universe
+    // to get reproducible measures
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self
+    }
+
+    // with_strict_mode makes the steps return an Error::ProbabilityDrift
+    // if the total probability drifts by more than the tolerance during a step,
+    // the probability discarded by the pruning policy is not counted as a drift
+    pub fn with_strict_mode(mut self, tolerance: f64) -> Self {
+        self.probability_tolerance = Some(tolerance);
+        self
+    }
+
+    // total_probability returns the sum of the probabilities of the configurations,
+    // which stays 1 for a normalized state unless some probability is pruned
+    pub fn total_probability(&self) -> f64 {
+        self.state
+            .iter()
+            .map(|configuration| configuration.amplitude.norm_sqr())
+            .sum()
+    }
+
+    // current_rules returns the rules that apply on the current step,
+    // or an InvalidRules error if the rules cycle is empty
+    pub fn current_rules(&self) -> Result<&Rules, Error> {
+        if self.rules.is_empty() {
+            return Err(Error::InvalidRules(RulesError::Empty));
+        }
+        Ok(&self.rules[self.step_count % self.rules.len()])
+    }
+
+    // into_storage converts the universe to another storage of the living cells
+    // e.g. universe.into_storage::<TiledCells>()
+    pub fn into_storage<D: LivingCells>(self) -> Universe<D> {
+        let state = self
+            .state
+            .into_iter()
+            .map(|configuration| Configuration {
+                amplitude: configuration.amplitude,
+                living_cells: D::from_unsorted(configuration.living_cells.cells().collect()),
+            })
+            .collect();
+
+        Universe {
+            state,
+            combined_state: self.combined_state,
+            is_even_step: self.is_even_step,
+            rules: self.rules,
+            step_count: self.step_count,
+            topology: self.topology,
+            pruning: self.pruning,
+            probability_tolerance: self.probability_tolerance,
+            history: self.history,
+            rng: self.rng,
+        }
+    }
 }
 
-// get_test_rules return an array of rules
-// at term the rules should normally be extrated from a file
-// but for now these rules are written in the code
-// and used for testing purposes
-pub fn get_test_rules() -> Rules {
-    let c = |x: f64, y: f64| -> Complex<f64> { Complex::new(x, y) };
+// validate_amplitudes checks that the amplitudes of the state are finite
+// and that at least one configuration has a non zero probability
+pub fn validate_amplitudes<C>(state: &State<C>) -> Result<(), AmplitudesError> {
+    let mut total_probability = 0.;
+    for (i, configuration) in state.iter().enumerate() {
+        if !configuration.amplitude.is_finite() {
+            return Err(AmplitudesError::NonFinite { configuration: i });
+        }
+        total_probability += configuration.amplitude.norm_sqr();
+    }
 
-    [
-        [
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., PI / 4.0).exp(),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1. / 2.0_f64.sqrt(), 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1. / 2.0_f64.sqrt(), 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1.0 / 2.0_f64.sqrt(), 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(-1.0 / 2.0_f64.sqrt(), 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., PI / 8.0).exp(),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(1., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-        ],
-        [
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., 0.),
-            c(0., PI / 2.0).exp(),
-        ],
-    ]
+    if total_probability > 0. {
+        Ok(())
+    } else {
+        Err(AmplitudesError::ZeroTotalProbability)
+    }
 }
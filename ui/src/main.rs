@@ -6,12 +6,25 @@ mod sketch;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// The path to the file containing the initial state of the universe
+    /// The path to the file containing the initial state of the universe,
+    /// or a snapshot of the universe saved with the save button
     #[clap(value_name = "STATE_FILE", index = 1)]
     state_file: Option<String>,
+
+    /// The path to the file containing the rules of the universe (JSON or TOML)
+    #[clap(short, long, value_name = "RULES_FILE")]
+    rules: Option<String>,
+
+    /// The seed of the random number generator used by the measures
+    #[clap(long, value_name = "SEED")]
+    seed: Option<u64>,
+
+    /// Normalize the amplitudes of the state so that its total probability is 1
+    #[clap(long)]
+    normalize: bool,
 }
 
 fn main() {
     let args = Args::parse();
-    sketch::run(args.state_file);
+    sketch::run(args.state_file, args.rules, args.seed, args.normalize);
 }
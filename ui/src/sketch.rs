@@ -1,4 +1,5 @@
-use core::universe::types::{Configuration, Coordinates, Universe};
+use core::universe::files;
+use core::universe::types::{Configuration, Coordinates, LoadOptions, Universe};
 use lazy_static::lazy_static;
 use nannou::{draw::mesh::vertex::Color, glam::Vec2, prelude::*, state::mouse::ButtonPosition};
 use nannou_egui::{self, egui, Egui};
@@ -6,6 +7,9 @@ use std::sync::Mutex;
 
 lazy_static! {
     static ref STATE_FILE: Mutex<String> = Mutex::new(String::new());
+    static ref RULES_FILE: Mutex<Option<String>> = Mutex::new(None);
+    static ref SEED: Mutex<Option<u64>> = Mutex::new(None);
+    static ref NORMALIZE: Mutex<bool> = Mutex::new(false);
 }
 
 const WIDTH: u32 = 1024;
@@ -38,17 +42,28 @@ pub struct Model {
     pub show_rules_squares: bool,
     pub show_numbers: bool,
     pub universe_file: Option<String>,
+    pub rules_file: Option<String>,
+    pub seed: Option<u64>,
+    pub normalize: bool,
     pub universe_measure_max: usize,
     pub universe: Universe,
     pub selected_configuration: Option<usize>,
     pub configurations_max: usize,
 }
 
-pub fn run(state_file: Option<String>) {
+pub fn run(
+    state_file: Option<String>,
+    rules_file: Option<String>,
+    seed: Option<u64>,
+    normalize: bool,
+) {
     *STATE_FILE.lock().unwrap() = match state_file {
         Some(sf) => sf,
         None => "".to_string(),
     };
+    *RULES_FILE.lock().unwrap() = rules_file;
+    *SEED.lock().unwrap() = seed;
+    *NORMALIZE.lock().unwrap() = normalize;
     nannou::app(model).update(update).view(view).run();
 }
 
@@ -80,33 +95,47 @@ fn update_ui(model: &mut Model) {
                 State::Paused => {
                     if ui.button("Reset").clicked() {
                         model.selected_configuration = None;
-                        model.universe = match &model.universe_file {
+                        let universe = match &model.universe_file {
                             Some(universe_file) => {
-                                Universe::new_from_files(universe_file.as_str()).unwrap()
+                                load_universe(universe_file, &model.rules_file, model.normalize)
                             }
                             None => {
                                 model.state = State::Drawing;
-                                Universe::new()
+                                new_empty_universe(&model.rules_file)
                             }
-                        }
+                        };
+                        model.universe = with_seed(universe, model.seed);
                     }
                     if ui.button("Run").clicked() {
                         model.state = State::Running;
                     }
                     if ui.button("Step").clicked() {
-                        model.universe.step();
-
-                        if model.auto_measure
-                            && model.universe.state.len() > model.universe_measure_max
-                        {
-                            model.universe.measure();
-                            model.selected_configuration = None;
+                        step_universe(model);
+                    }
+                    if ui.button("Step back").clicked() {
+                        if let Err(err) = model.universe.step_back() {
+                            eprintln!("can't step back: {err}");
                         }
                     }
                     if ui.button("Measure").clicked() {
-                        model.universe.measure();
+                        if let Err(err) = model.universe.measure() {
+                            eprintln!("can't measure the universe: {err}");
+                        }
                         model.selected_configuration = None;
                     }
+                    if ui.button("Normalize").clicked() {
+                        if let Err(err) = model.universe.normalize() {
+                            eprintln!("can't normalize the universe: {err}");
+                        }
+                    }
+                    if ui.button("Save").clicked() {
+                        let snapshot_file =
+                            format!("snapshot_step_{}.json", model.universe.step_count);
+                        match model.universe.save(&snapshot_file) {
+                            Ok(()) => println!("universe saved in {snapshot_file}"),
+                            Err(err) => eprintln!("can't save universe in {snapshot_file}: {err}"),
+                        }
+                    }
                 }
             });
             ui.separator();
@@ -130,6 +159,10 @@ fn update_ui(model: &mut Model) {
                 "Configurations count: {}",
                 model.universe.state.len()
             ));
+            ui.label(format!(
+                "Total probability: {:.6}",
+                model.universe.total_probability()
+            ));
             ui.add_space(4.0);
             ui.checkbox(&mut model.show_numbers, "Show numbers");
             let row_height = 10.;
@@ -181,14 +214,18 @@ fn model(app: &App) -> Model {
     // If a state file is provided, we use it to create the universe
     // Else we create an empty universe in which we can draw cells
     let state_file = STATE_FILE.lock().unwrap();
+    let rules_file = RULES_FILE.lock().unwrap().clone();
+    let seed = *SEED.lock().unwrap();
+    let normalize = *NORMALIZE.lock().unwrap();
     let (universe, universe_file, state) = match state_file.as_str() {
-        "" => (Universe::new(), None, State::Drawing),
+        "" => (new_empty_universe(&rules_file), None, State::Drawing),
         sf => (
-            Universe::new_from_files(sf).unwrap(),
+            load_universe(sf, &rules_file, normalize),
             Some(sf.to_string()),
             State::Running,
         ),
     };
+    let universe = with_seed(universe, seed);
 
     let win_w = app.window_rect().w();
     let win_h = app.window_rect().h();
@@ -211,6 +248,9 @@ fn model(app: &App) -> Model {
         show_rules_squares: false,
         show_numbers: false,
         universe_file,
+        rules_file,
+        seed,
+        normalize,
         universe_measure_max: 128,
         selected_configuration: None,
         universe,
@@ -218,6 +258,62 @@ fn model(app: &App) -> Model {
     }
 }
 
+// Load a universe from a state file or a snapshot file saved with the save button
+// The rules of the rules file are used with a state file if one is provided
+// A warning is printed if the state isn't normalized and normalize is false
+fn load_universe(universe_file: &str, rules_file: &Option<String>, normalize: bool) -> Universe {
+    let options = LoadOptions { normalize };
+    let universe = match rules_file {
+        Some(rules_file) => {
+            Universe::new_from_files_with(universe_file, Some(rules_file), options).unwrap()
+        }
+        None => Universe::load_with(universe_file, options).unwrap(),
+    };
+    if let Err(warning) = universe.validate_normalization() {
+        eprintln!("warning: {warning}, run with --normalize to normalize the state");
+    }
+    universe
+}
+
+// Create a universe with no living cell in which we can draw cells
+fn new_empty_universe(rules_file: &Option<String>) -> Universe {
+    match rules_file {
+        Some(rules_file) => Universe::new_from_state(
+            Universe::new().state,
+            files::get_rules_from_file(rules_file).unwrap(),
+        )
+        .unwrap(),
+        None => Universe::new(),
+    }
+}
+
+// Seed the random number generator of the universe if a seed is provided
+// so that resetting the universe replays the same measures
+fn with_seed(universe: Universe, seed: Option<u64>) -> Universe {
+    match seed {
+        Some(seed) => universe.with_seed(seed),
+        None => universe,
+    }
+}
+
+// Step the universe and measure it if auto measure is enabled and
+// there are too many configurations, the simulation is paused on error
+fn step_universe(model: &mut Model) {
+    let result = model.universe.step().and_then(|_| {
+        if model.auto_measure && model.universe.state.len() > model.universe_measure_max {
+            model.selected_configuration = None;
+            model.universe.measure()
+        } else {
+            Ok(())
+        }
+    });
+
+    if let Err(err) = result {
+        eprintln!("can't step the universe: {err}");
+        model.state = State::Paused;
+    }
+}
+
 fn update(app: &App, model: &mut Model, _update: Update) {
     match model.state {
         State::Drawing => update_drawing(app, model),
@@ -229,12 +325,7 @@ fn update(app: &App, model: &mut Model, _update: Update) {
             if app.elapsed_frames() % frame_to_skip != 0 {
                 return;
             } else {
-                model.universe.step();
-
-                if model.auto_measure && model.universe.state.len() > model.universe_measure_max {
-                    model.universe.measure();
-                    model.selected_configuration = None;
-                }
+                step_universe(model);
             }
         }
         State::Paused => (),
@@ -254,9 +345,13 @@ fn update_drawing(app: &App, model: &mut Model) {
             if matches!(model.draw_state, DrawState::NoClick) {
                 model.draw_state = match get_cell_coordinates(click_pos, model) {
                     Some(cell_coordinates) => {
-                        match model.universe.state[0].living_cells.get(&cell_coordinates) {
-                            Some(_) => DrawState::Deleting,
-                            None => DrawState::Creating,
+                        if model.universe.state[0]
+                            .living_cells
+                            .contains(&cell_coordinates)
+                        {
+                            DrawState::Deleting
+                        } else {
+                            DrawState::Creating
                         }
                     }
                     None => DrawState::NoDraw,
@@ -267,20 +362,16 @@ fn update_drawing(app: &App, model: &mut Model) {
                 let mouse_pos = app.mouse.position();
 
                 if let Some(cell_coordinates) = get_cell_coordinates(&mouse_pos, model) {
-                    match model.universe.state[0].living_cells.get(&cell_coordinates) {
-                        Some(_) => {
-                            if matches!(model.draw_state, DrawState::Deleting) {
-                                model.universe.state[0]
-                                    .living_cells
-                                    .remove(&cell_coordinates);
-                            }
+                    match model.draw_state {
+                        DrawState::Deleting => {
+                            model.universe.state[0]
+                                .living_cells
+                                .remove(&cell_coordinates);
                         }
-                        None => {
-                            if matches!(model.draw_state, DrawState::Creating) {
-                                model.universe.state[0]
-                                    .living_cells
-                                    .insert(cell_coordinates, false);
-                            }
+                        _ => {
+                            model.universe.state[0]
+                                .living_cells
+                                .insert(cell_coordinates);
                         }
                     };
                     model.universe.compute_combined_state();
@@ -372,8 +463,11 @@ fn draw_combined_state(i: i32, j: i32, universe: &Universe, gdraw: &Draw, m: &Mo
 }
 
 fn draw_configuration(i: i32, j: i32, configuration: &Configuration, gdraw: &Draw, m: &Model) {
-    match configuration.living_cells.get(&Coordinates { x: i, y: j }) {
-        Some(_) => {
+    match configuration
+        .living_cells
+        .contains(&Coordinates { x: i, y: j })
+    {
+        true => {
             //This required because of nannou's coordinate system
             //where the origin is the center of the window
             //and the y axis is inverted, so we need to have a negative
@@ -389,7 +483,7 @@ fn draw_configuration(i: i32, j: i32, configuration: &Configuration, gdraw: &Dra
                 .w_h(m.block_size, m.block_size)
                 .color(green);
         }
-        None => {
+        false => {
             //This required because of nannou's coordinate system
             //where the origin is the center of the window
             //and the y axis is inverted, so we need to have a negative
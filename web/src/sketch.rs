@@ -25,7 +25,7 @@ impl Model {
             .unwrap()
         } else {
             let state_file = "./core/fixtures/state_2_diagonal_cells.json";
-            Universe::new_from_files(state_file).unwrap()
+            Universe::new_from_files(state_file, None).unwrap()
         };
 
         let win_w = app.window_rect().w();
@@ -58,9 +58,9 @@ fn update(app: &App, model: &mut Model, _update: Update) {
     }
 
     if model.universe.state.len() > 128 {
-        model.universe.measure();
+        model.universe.measure().unwrap();
     }
-    model.universe.step();
+    model.universe.step().unwrap();
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {